@@ -0,0 +1,156 @@
+use bytes::{Bytes, BytesMut};
+use log::debug;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::Notify;
+
+/// Memory uploads of a client may hold at once by default.
+pub const DEFAULT_MEMORY_BUDGET: u64 = 512 * 1024 * 1024;
+
+/// Upload buffers shared by every transfer of a client, bounded by a memory budget.
+///
+/// Buffers are reused once their part is sent, so steady uploads don't reallocate. A buffer
+/// larger than the whole budget is still handed out when nothing else is held, so a small
+/// budget slows uploads down instead of blocking them.
+pub(crate) struct BufferPool {
+    budget: u64,
+    state: Mutex<PoolState>,
+    released: Notify,
+}
+
+#[derive(Default)]
+struct PoolState {
+    // Capacity of the buffers handed out
+    in_use: u64,
+    // Returned buffers waiting to be reused, they count against the budget too
+    free: Vec<BytesMut>,
+}
+
+impl PoolState {
+    fn free_bytes(&self) -> u64 {
+        self.free.iter().map(|b| b.capacity() as u64).sum()
+    }
+}
+
+impl BufferPool {
+    pub(crate) fn new(budget: u64) -> Arc<Self> {
+        Arc::new(Self {
+            budget,
+            state: Mutex::default(),
+            released: Notify::new(),
+        })
+    }
+
+    /// Waits until `size` bytes fit in the budget and returns an empty buffer of that capacity.
+    pub(crate) async fn get(self: &Arc<Self>, size: usize) -> PooledBuffer {
+        loop {
+            // Registered before checking, so a release in between isn't missed
+            let released = self.released.notified();
+            if let Some(buffer) = self.try_get(size) {
+                return PooledBuffer {
+                    reserved: buffer.capacity() as u64,
+                    buffer: Some(buffer),
+                    size,
+                    pool: Arc::clone(self),
+                };
+            }
+            released.await;
+        }
+    }
+
+    fn try_get(&self, size: usize) -> Option<BytesMut> {
+        let mut state = self.state.lock().unwrap();
+
+        let reusable = state.free.iter().position(|b| b.capacity() >= size);
+        if let Some(index) = reusable {
+            let buffer = state.free.swap_remove(index);
+            if state.in_use > 0 && state.in_use + buffer.capacity() as u64 > self.budget {
+                state.free.push(buffer);
+                return None;
+            }
+            state.in_use += buffer.capacity() as u64;
+            return Some(buffer);
+        }
+
+        if state.in_use > 0 && state.in_use + size as u64 > self.budget {
+            return None;
+        }
+        // Idle buffers too small to reuse make room for the new one
+        while !state.free.is_empty()
+            && state.in_use + state.free_bytes() + size as u64 > self.budget
+        {
+            state.free.pop();
+        }
+        state.in_use += size as u64;
+        debug!("Allocating a {size} bytes upload buffer");
+        Some(BytesMut::with_capacity(size))
+    }
+
+    fn release(&self, buffer: Option<BytesMut>, reserved: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.in_use -= reserved;
+        if let Some(mut buffer) = buffer
+            && state.in_use + state.free_bytes() + buffer.capacity() as u64 <= self.budget
+        {
+            buffer.clear();
+            state.free.push(buffer);
+        }
+        drop(state);
+        self.released.notify_waiters();
+    }
+}
+
+/// A buffer from a [`BufferPool`], its memory is given back to the pool when dropped.
+pub(crate) struct PooledBuffer {
+    // None while frozen and sent
+    buffer: Option<BytesMut>,
+    // Bytes asked for, a reused buffer can have more capacity
+    size: usize,
+    // Capacity counted against the budget
+    reserved: u64,
+    pool: Arc<BufferPool>,
+}
+
+impl PooledBuffer {
+    pub(crate) fn len(&self) -> usize {
+        self.buffer.as_ref().map_or(0, |b| b.len())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fills the buffer up to the size asked for from `reader`, stopping early if it ends.
+    pub(crate) async fn fill_from<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+    ) -> std::io::Result<()> {
+        let buffer = self.buffer.as_mut().expect("buffer was frozen");
+        // Reads never go past the capacity, which would reallocate
+        while buffer.len() < self.size {
+            let limit = (self.size - buffer.len()) as u64;
+            if (&mut *reader).take(limit).read_buf(buffer).await? == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The content, cheap to clone for sending. Give it back with [`PooledBuffer::reclaim`].
+    pub(crate) fn freeze(&mut self) -> Bytes {
+        self.buffer.take().expect("buffer was frozen").freeze()
+    }
+
+    /// Takes back frozen content once every clone of it is dropped, so it can be reused.
+    pub(crate) fn reclaim(&mut self, content: Bytes) {
+        // Still shared (e.g. by a connection) means it's simply freed instead
+        self.buffer = content.try_into_mut().ok();
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        // The reservation is released whether or not the buffer came back
+        self.pool.release(self.buffer.take(), self.reserved);
+    }
+}
@@ -1,4 +1,10 @@
+use crate::output::OutputFormat;
+use bytesize::ByteSize;
 use clap::{Args, Parser, Subcommand};
+use obsctl::{
+    AddressingStyle, DEFAULT_CHUNK_SIZE, DEFAULT_MEMORY_BUDGET, DEFAULT_MULTIPART_THRESHOLD,
+    MetadataDirective, SymlinkPolicy,
+};
 
 // The message that appears when you use "--help"
 const APP_HELP_TEMPLATE: &str = r"
@@ -38,6 +44,50 @@ pub struct CliArgs {
     /// Optional secret key override. Use only if env var and credentials CSV are unavailable.
     #[arg(short, long, global = true)]
     pub sk: Option<String>,
+
+    /// Custom OBS endpoint (e.g., obs.example.com or http://localhost:9000). Defaults to the region's public endpoint.
+    #[arg(short, long, global = true)]
+    pub endpoint: Option<String>,
+
+    /// Bucket addressing style in URLs: 'virtual' (bucket.host/key) or 'path' (host/bucket/key).
+    #[arg(long, global = true)]
+    pub addressing_style: Option<AddressingStyle>,
+
+    /// Use plain HTTP instead of HTTPS. Only for legacy setups, requests and data travel unencrypted.
+    #[arg(long, global = true)]
+    pub insecure_http: bool,
+
+    /// PEM bundle of extra CA certificates to trust, e.g. for corporate TLS-intercepting proxies.
+    #[arg(long, global = true)]
+    pub ca_bundle: Option<String>,
+
+    /// PEM client certificate for mutual TLS. Requires --client-key.
+    #[arg(long, global = true, requires = "client_key")]
+    pub client_cert: Option<String>,
+
+    /// PKCS#8 PEM private key of the client certificate. Requires --client-cert.
+    #[arg(long, global = true, requires = "client_cert")]
+    pub client_key: Option<String>,
+
+    /// Attempts per request (including the first) before giving up on network errors or throttling. 1 disables retries.
+    #[arg(long, global = true, default_value_t = 5)]
+    pub max_attempts: u32,
+
+    /// Initial delay between retries in milliseconds, doubled on every attempt.
+    #[arg(long, global = true, default_value_t = 200)]
+    pub retry_backoff_ms: u64,
+
+    /// Wait the exact backoff instead of a random delay up to it.
+    #[arg(long, global = true)]
+    pub no_retry_jitter: bool,
+
+    /// File data uploads may hold in memory at once, shared by all files of a command (e.g. 256MiB).
+    #[arg(long, global = true, default_value_t = ByteSize(DEFAULT_MEMORY_BUDGET))]
+    pub max_memory: ByteSize,
+
+    /// Output format. Tables are logged to stderr, the other formats are printed to stdout for scripts.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
 }
 
 // TODO setup, for ak/sk
@@ -57,22 +107,58 @@ pub enum Commands {
     #[command(visible_alias = "rmb")]
     DeleteBucket(DeleteBucketArgs),
 
+    /// Check that a bucket exists and show its region and type
+    #[command()]
+    HeadBucket(HeadBucketArgs),
+
     /// List objects in a bucket
     #[command(visible_alias = "ls")]
     ListObjects(ListObjectsArgs),
 
     /// Upload one or more objects to a bucket
     #[command(visible_alias = "put")]
-    UploadObject(UploadObjectArgs),
+    UploadObject(Box<UploadObjectArgs>),
 
     /// Download objects contents to disk
     #[command(visible_alias = "get")]
     DownloadObject(DownloadObjectArgs),
 
+    /// Print an object's contents to stdout
+    #[command()]
+    Cat(CatArgs),
+
+    /// Show an object's metadata without downloading it
+    #[command(visible_alias = "stat")]
+    HeadObject(HeadObjectArgs),
+
     /// Delete an object from a bucket
     #[command(visible_alias = "rm")]
     DeleteObject(DeleteObjectArgs),
 
+    /// Copy an object within OBS, without downloading it
+    #[command(visible_alias = "cp")]
+    CopyObject(CopyObjectArgs),
+
+    /// Move (rename) an object within OBS, deleting the source once the copy is verified
+    #[command(visible_alias = "mv")]
+    MoveObject(CopyObjectArgs),
+
+    /// List multipart uploads that were never completed or aborted
+    #[command(visible_alias = "lsu")]
+    ListUploads(ListUploadsArgs),
+
+    /// List the parts uploaded to a multipart upload
+    #[command()]
+    ListParts(ListPartsArgs),
+
+    /// Abort a multipart upload, or every upload older than an age
+    #[command()]
+    AbortUpload(AbortUploadArgs),
+
+    /// Mirror a local directory to a bucket prefix or the other way around
+    #[command()]
+    Sync(SyncArgs),
+
     /// List Huawei Cloud regions
     #[command(visible_alias = "regions")]
     ListRegions,
@@ -100,6 +186,24 @@ pub struct ListObjectsArgs {
     /// List results after the object with the marker
     #[arg(short, long)]
     pub marker: Option<String>,
+    /// Follow pagination until every object is listed, instead of only the first page
+    #[arg(long)]
+    pub all: bool,
+    /// Stop after listing this many objects, following pagination if needed
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+    /// Objects requested per page (1-1000)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=1000))]
+    pub max_keys: Option<u32>,
+    /// Print each page as soon as it arrives instead of waiting for the whole listing. Implies --all
+    #[arg(long)]
+    pub stream: bool,
+    /// Group keys up to this character into folders (common prefixes)
+    #[arg(long)]
+    pub delimiter: Option<String>,
+    /// Directory view: list the folders and objects directly under --prefix, like 'ls'. Uses '/' as delimiter unless --delimiter is set
+    #[arg(short = 'D', long)]
+    pub dir: bool,
 }
 
 #[derive(Args)]
@@ -109,16 +213,82 @@ pub struct DeleteBucketArgs {
     pub buckets: Vec<String>,
 }
 
+#[derive(Args)]
+pub struct HeadBucketArgs {
+    /// The bucket to check
+    pub bucket: String,
+}
+
 #[derive(Args)]
 pub struct UploadObjectArgs {
     /// The bucket to upload to
     pub bucket: String,
-    /// One or more local file paths to upload. The object key will be the filename.
-    #[arg(short = 'f', long = "file-path", num_args(1..))]
+    /// One or more local file paths to upload. The object key will be the filename. Use '-' to upload stdin (requires --object-path)
+    #[arg(short = 'f', long = "file-path", num_args(1..), required_unless_present = "recursive")]
     pub file_paths: Vec<String>,
     /// Optional object path for single-file uploads
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "recursive")]
     pub object_path: Option<String>,
+    /// Upload every file under this directory, keeping their relative paths as keys
+    #[arg(long, value_name = "DIR", conflicts_with = "file_paths")]
+    pub recursive: Option<String>,
+    /// Key prefix (destination "folder") for recursive uploads
+    #[arg(short, long, requires = "recursive", default_value = "")]
+    pub prefix: String,
+    /// Only upload files whose relative path matches this glob, '*' stays within a folder and '**' crosses them, e.g. '**/*.log' (repeatable)
+    #[arg(long, requires = "recursive")]
+    pub include: Vec<String>,
+    /// Skip files whose relative path matches this glob, '*' stays within a folder and '**' crosses them (repeatable)
+    #[arg(long, requires = "recursive")]
+    pub exclude: Vec<String>,
+    /// Symbolic links in recursive uploads: 'follow' or 'skip'
+    #[arg(long, requires = "recursive", default_value_t = SymlinkPolicy::Follow)]
+    pub symlinks: SymlinkPolicy,
+    /// Continue interrupted uploads from their checkpoint, only uploading missing parts
+    #[arg(long)]
+    pub resume: bool,
+    #[command(flatten)]
+    pub parts: PartArgs,
+    /// Content type of the objects (e.g., text/html), guessed from their extension by default
+    #[arg(long)]
+    pub content_type: Option<String>,
+    /// Cache-Control header served with the objects (e.g., "max-age=3600")
+    #[arg(long)]
+    pub cache_control: Option<String>,
+    /// Content-Disposition header served with the objects (e.g., attachment)
+    #[arg(long)]
+    pub content_disposition: Option<String>,
+    /// Content-Encoding of the objects, for files that are already compressed (e.g., gzip)
+    #[arg(long)]
+    pub content_encoding: Option<String>,
+    /// Metadata of the objects as NAME=VALUE, sent as x-obs-meta-NAME (repeatable)
+    #[arg(long, value_parser = parse_key_value)]
+    pub meta: Vec<(String, String)>,
+}
+
+// Multipart settings shared by the commands that upload files
+#[derive(Args)]
+pub struct PartArgs {
+    /// Files this size or larger are uploaded in parts (e.g., 8MiB), smaller ones with a single request
+    #[arg(long, default_value_t = ByteSize(DEFAULT_MULTIPART_THRESHOLD))]
+    pub multipart_threshold: ByteSize,
+    /// Size of each part (100KiB to 5GiB, default 50MiB), raised automatically for files that need more than 10,000 parts
+    #[arg(long)]
+    pub part_size: Option<ByteSize>,
+    /// Parts of a single file uploaded at the same time (default 32)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=256))]
+    pub part_concurrency: Option<u64>,
+}
+
+// Ranged download settings shared by the commands that download objects
+#[derive(Args)]
+pub struct ChunkArgs {
+    /// Objects larger than this are downloaded as concurrent ranges of this size (e.g., 16MiB)
+    #[arg(long, default_value_t = ByteSize(DEFAULT_CHUNK_SIZE))]
+    pub chunk_size: ByteSize,
+    /// Ranges of a single object downloaded at the same time
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u64).range(1..=256))]
+    pub chunk_concurrency: u64,
 }
 
 #[derive(Args)]
@@ -126,11 +296,122 @@ pub struct DownloadObjectArgs {
     /// The bucket to download from
     pub bucket: String,
     /// Object path in bucket
-    #[arg(short, long)]
-    pub object_path: String,
+    #[arg(short, long, required_unless_present = "recursive")]
+    pub object_path: Option<String>,
     /// Output directory, NOT the filename
     #[arg(short = 'd', long)]
     pub output_dir: Option<String>,
+    /// Download every object under --prefix, mirroring their paths as directories
+    #[arg(long, requires = "prefix", conflicts_with = "object_path")]
+    pub recursive: bool,
+    /// Key prefix to download with --recursive (e.g., logs/2026/)
+    #[arg(short, long, requires = "recursive")]
+    pub prefix: Option<String>,
+    /// Objects downloaded at the same time with --recursive
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u64).range(1..=256))]
+    pub concurrency: u64,
+    #[command(flatten)]
+    pub chunks: ChunkArgs,
+    /// Write the object to stdout instead of a file, like 'cat'
+    #[arg(long, conflicts_with_all = ["output_dir", "recursive"])]
+    pub stdout: bool,
+}
+
+#[derive(Args)]
+pub struct CatArgs {
+    /// The bucket to download from
+    pub bucket: String,
+    /// Object path in bucket
+    #[arg(short, long)]
+    pub object_path: String,
+}
+
+#[derive(Args)]
+pub struct HeadObjectArgs {
+    /// The bucket of the object
+    pub bucket: String,
+    /// Object path in bucket
+    #[arg(short, long)]
+    pub object_path: String,
+}
+
+#[derive(Args)]
+pub struct ListUploadsArgs {
+    /// The bucket to list uploads from
+    pub bucket: String,
+    /// Include only uploads of keys with the specified prefix
+    #[arg(short, long)]
+    pub prefix: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ListPartsArgs {
+    /// The bucket of the upload
+    pub bucket: String,
+    /// Object path of the upload
+    #[arg(short, long)]
+    pub object_path: String,
+    /// Id of the upload, see list-uploads
+    #[arg(short, long)]
+    pub upload_id: String,
+}
+
+#[derive(Args)]
+pub struct AbortUploadArgs {
+    /// The bucket of the upload
+    pub bucket: String,
+    /// Object path of the upload to abort
+    #[arg(
+        short,
+        long,
+        requires = "upload_id",
+        required_unless_present = "older_than"
+    )]
+    pub object_path: Option<String>,
+    /// Id of the upload to abort, see list-uploads
+    #[arg(short, long, requires = "object_path")]
+    pub upload_id: Option<String>,
+    /// Abort every upload initiated longer ago than this (e.g., 90m, 12h or 7d)
+    #[arg(long, value_parser = parse_age, conflicts_with = "object_path")]
+    pub older_than: Option<chrono::Duration>,
+}
+
+#[derive(Args)]
+pub struct CopyObjectArgs {
+    /// Object to copy, as obs://bucket/key
+    pub source: String,
+    /// Where to copy it, as obs://bucket/key. Keys ending in '/' keep the source's name
+    pub destination: String,
+    /// Keep the source's metadata ('copy') or set it from --content-type and --meta ('replace')
+    #[arg(long)]
+    pub metadata_directive: Option<MetadataDirective>,
+    /// Content type of the copy, implies --metadata-directive replace
+    #[arg(long)]
+    pub content_type: Option<String>,
+    /// Metadata of the copy as NAME=VALUE, sent as x-obs-meta-NAME (repeatable). Implies --metadata-directive replace
+    #[arg(long, value_parser = parse_key_value)]
+    pub meta: Vec<(String, String)>,
+}
+
+#[derive(Args)]
+pub struct SyncArgs {
+    /// Where files are copied from, a local directory or obs://bucket/prefix
+    pub source: String,
+    /// Where files are copied to, a local directory or obs://bucket/prefix
+    pub destination: String,
+    /// Delete destination files or objects that don't exist in the source
+    #[arg(long)]
+    pub delete: bool,
+    /// Only show what would be transferred or deleted
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Files transferred at the same time
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u64).range(1..=256))]
+    pub concurrency: u64,
+    #[command(flatten)]
+    pub parts: PartArgs,
+    #[command(flatten)]
+    pub chunks: ChunkArgs,
 }
 
 #[derive(Args)]
@@ -138,6 +419,81 @@ pub struct DeleteObjectArgs {
     /// The bucket where the object is
     pub bucket: String,
     /// Object path in bucket
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["recursive", "glob"],
+        conflicts_with_all = ["recursive", "glob", "prefix"]
+    )]
+    pub object_path: Option<String>,
+    /// Delete every object under --prefix, in batches of up to 1000 keys
+    #[arg(long, requires = "prefix")]
+    pub recursive: bool,
+    /// Key prefix ("folder") deleted with --recursive, or searched for --glob matches
     #[arg(short, long)]
-    pub object_path: String,
+    pub prefix: Option<String>,
+    /// Delete the keys matching this glob, '*' stays within a folder and '**' crosses them, e.g. 'logs/**/*.gz' (repeatable)
+    #[arg(short, long)]
+    pub glob: Vec<String>,
+    /// Only report keys that failed to be deleted
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+// Parses NAME=VALUE pairs
+fn parse_key_value(pair: &str) -> Result<(String, String), String> {
+    match pair.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("invalid '{pair}', expected NAME=VALUE")),
+    }
+}
+
+// Parses ages like 90s, 30m, 12h or 7d
+fn parse_age(age: &str) -> Result<chrono::Duration, String> {
+    let split = age.len() - age.chars().last().map_or(0, char::len_utf8);
+    let (amount, unit) = age.split_at(split);
+    let amount = amount
+        .parse::<i64>()
+        .map_err(|_| format!("invalid age '{age}', expected e.g. 90m, 12h or 7d"))?;
+    // A negative age puts the cutoff in the future, matching uploads still in progress
+    if amount < 0 {
+        return Err(format!("invalid age '{age}', it can't be negative"));
+    }
+
+    let duration = match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        _ => return Err(format!("unknown unit in '{age}', expected s, m, h or d")),
+    };
+    duration.ok_or_else(|| format!("age '{age}' is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_age_reads_each_unit() {
+        assert_eq!(parse_age("90s"), Ok(chrono::Duration::seconds(90)));
+        assert_eq!(parse_age("30m"), Ok(chrono::Duration::minutes(30)));
+        assert_eq!(parse_age("12h"), Ok(chrono::Duration::hours(12)));
+        assert_eq!(parse_age("7d"), Ok(chrono::Duration::days(7)));
+        assert_eq!(parse_age("0d"), Ok(chrono::Duration::zero()));
+    }
+
+    #[test]
+    fn parse_age_rejects_invalid_ages() {
+        for age in ["", "d", "7", "7w", "1.5d", "-1d", "ä"] {
+            assert!(parse_age(age).is_err(), "{age}");
+        }
+    }
+
+    #[test]
+    fn parse_age_rejects_overflowing_ages() {
+        assert!(parse_age("999999999999999d").is_err());
+        assert!(parse_age(&format!("{}s", i64::MAX)).is_err());
+        assert!(parse_age("99999999999999999999d").is_err());
+    }
 }
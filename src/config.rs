@@ -4,7 +4,7 @@ use log::info;
 use std::fs::OpenOptions;
 use std::io::Write;
 
-use crate::fuzzy_match_region;
+use obsctl::region::fuzzy_match_region;
 
 pub fn set_basic_configs() -> Result<()> {
     info!(
@@ -26,21 +26,39 @@ pub fn set_basic_configs() -> Result<()> {
         .interact_text()
         .context("Invalid input")?;
 
-    region = fuzzy_match_region(&region);
+    region = fuzzy_match_region(&region)?;
 
-    let lines_unix = format!(
+    let endpoint: String = Input::new()
+        .with_prompt("Write your custom endpoint (leave empty for the public one)")
+        .allow_empty(true)
+        .interact_text()
+        .context("Invalid input")?;
+
+    let mut lines_unix = format!(
         "\nexport HUAWEICLOUD_SDK_AK=\"{}\"\nexport HUAWEICLOUD_SDK_SK=\"{}\"\nexport HUAWEICLOUD_SDK_REGION=\"{}\"\n",
         ak, sk, region
     );
 
     // EXPERIMENTAL Windows PowerShell format
-    let lines_windows = format!(
+    let mut lines_windows = format!(
         "\n[Environment]::SetEnvironmentVariable(\"HUAWEICLOUD_SDK_AK\", \"{}\", \"User\")\n\
          [Environment]::SetEnvironmentVariable(\"HUAWEICLOUD_SDK_SK\", \"{}\", \"User\")\n\
          [Environment]::SetEnvironmentVariable(\"HUAWEICLOUD_SDK_REGION\", \"{}\", \"User\")\n",
         ak, sk, region
     );
 
+    if !endpoint.trim().is_empty() {
+        let endpoint = endpoint.trim();
+        lines_unix.push_str(&format!(
+            "export HUAWEICLOUD_SDK_ENDPOINT=\"{}\"\n",
+            endpoint
+        ));
+        lines_windows.push_str(&format!(
+            "[Environment]::SetEnvironmentVariable(\"HUAWEICLOUD_SDK_ENDPOINT\", \"{}\", \"User\")\n",
+            endpoint
+        ));
+    }
+
     if cfg!(windows) {
         info!("Windows detected, writing environment variables");
 
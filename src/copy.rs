@@ -0,0 +1,365 @@
+use crate::endpoint;
+use crate::multipart::part_size_for;
+use crate::obs::{
+    Body, ContentType, MAX_PUT_SIZE, ObsClient, ObsRequest, ensure_success, metadata_headers,
+    transfer_bar,
+};
+use crate::xml::{Part, xml_text};
+use anyhow::{Context, Result, anyhow, bail};
+use colored::Colorize;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use log::{debug, info, warn};
+use reqwest::Method;
+use reqwest::header::{
+    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG,
+    EXPIRES, HeaderMap, HeaderName, HeaderValue,
+};
+use std::fmt;
+use std::str::FromStr;
+
+// Parts of copies over 5 GiB, no data goes through the client so large parts only save requests
+const COPY_PART_SIZE: u64 = 1024 * 1024 * 1024;
+// Parts copied at the same time
+const COPY_CONCURRENCY: usize = 8;
+// Standard headers that are part of an object's metadata, besides its content type
+const METADATA_HEADERS: [HeaderName; 4] = [
+    CACHE_CONTROL,
+    CONTENT_DISPOSITION,
+    CONTENT_ENCODING,
+    EXPIRES,
+];
+
+/// What a copy does with the metadata of the source object.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MetadataDirective {
+    /// Keep the content type and `x-obs-meta-*` metadata of the source
+    #[default]
+    Copy,
+    /// Use the metadata given in [`CopyOptions`] instead
+    Replace,
+}
+
+impl MetadataDirective {
+    fn as_header(&self) -> &'static str {
+        match self {
+            MetadataDirective::Copy => "COPY",
+            MetadataDirective::Replace => "REPLACE",
+        }
+    }
+}
+
+impl FromStr for MetadataDirective {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "copy" => Ok(MetadataDirective::Copy),
+            "replace" => Ok(MetadataDirective::Replace),
+            other => Err(anyhow!(
+                "Unknown metadata directive '{other}', expected 'copy' or 'replace'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MetadataDirective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataDirective::Copy => write!(f, "copy"),
+            MetadataDirective::Replace => write!(f, "replace"),
+        }
+    }
+}
+
+/// Settings for server-side copies.
+#[derive(Clone, Debug, Default)]
+pub struct CopyOptions {
+    pub metadata_directive: MetadataDirective,
+    /// Content type of the copy, only used with [`MetadataDirective::Replace`]
+    pub content_type: Option<String>,
+    /// User metadata of the copy, sent as `x-obs-meta-<name>`. Only used with
+    /// [`MetadataDirective::Replace`]
+    pub metadata: Vec<(String, String)>,
+}
+
+impl ObsClient {
+    /// Copies an object within OBS, within a bucket or between buckets, without downloading it.
+    ///
+    /// Objects up to 5 GiB are copied with a single request, larger ones part by part with
+    /// Upload Part - Copy. The copy fails if the source changes while it runs.
+    pub async fn copy_object(
+        &self,
+        src_bucket: &str,
+        src_key: &str,
+        dst_bucket: &str,
+        dst_key: &str,
+        options: &CopyOptions,
+    ) -> Result<()> {
+        if src_bucket == dst_bucket
+            && src_key == dst_key
+            && options.metadata_directive == MetadataDirective::Copy
+        {
+            bail!("Can't copy '{src_key}' onto itself without replacing its metadata");
+        }
+
+        let source = self.head_object(src_bucket, src_key).await?;
+        self.copy_from(src_bucket, src_key, &source, dst_bucket, dst_key, options)
+            .await?;
+        Ok(())
+    }
+
+    /// Moves (renames) an object, within a bucket or between buckets, without downloading it.
+    ///
+    /// The source is only deleted once the copy exists with the same size, and the same ETag
+    /// when it was copied with a single request.
+    pub async fn move_object(
+        &self,
+        src_bucket: &str,
+        src_key: &str,
+        dst_bucket: &str,
+        dst_key: &str,
+        options: &CopyOptions,
+    ) -> Result<()> {
+        if src_bucket == dst_bucket && src_key == dst_key {
+            bail!("Can't move '{src_key}' onto itself");
+        }
+
+        let source = self.head_object(src_bucket, src_key).await?;
+        let single_request = self
+            .copy_from(src_bucket, src_key, &source, dst_bucket, dst_key, options)
+            .await?;
+
+        let copy = self
+            .head_object(dst_bucket, dst_key)
+            .await
+            .context("Failed to verify the copy, the source was kept")?;
+        let (source_size, copy_size) = (content_length(&source)?, content_length(&copy)?);
+        if copy_size != source_size {
+            bail!(
+                "Copy of '{src_key}' has {copy_size} bytes instead of {source_size}, the source was kept"
+            );
+        }
+        // Multipart copies get an ETag of their own
+        if single_request && copy.get(ETAG) != source.get(ETAG) {
+            bail!("Copy of '{src_key}' has a different ETag, the source was kept");
+        }
+        debug!("Copy of '{src_key}' verified, deleting it");
+
+        self.delete_object(src_bucket, src_key)
+            .await
+            .with_context(|| format!("Copied to '{dst_key}' but failed to delete '{src_key}'"))
+    }
+
+    // Copies from a source described by its HEAD headers, true if done with a single request
+    async fn copy_from(
+        &self,
+        src_bucket: &str,
+        src_key: &str,
+        source: &HeaderMap,
+        dst_bucket: &str,
+        dst_key: &str,
+        options: &CopyOptions,
+    ) -> Result<bool> {
+        let size = content_length(source)?;
+        // Copies only go ahead if the source is still the object that was looked at
+        let mut copy_headers = HeaderMap::new();
+        copy_headers.insert(
+            "x-obs-copy-source",
+            HeaderValue::from_str(&endpoint::canonical_resource(Some(src_bucket), src_key, ""))?,
+        );
+        if let Some(etag) = source.get(ETAG) {
+            copy_headers.insert("x-obs-copy-source-if-match", etag.clone());
+        }
+
+        if size <= MAX_PUT_SIZE {
+            self.copy_single(dst_bucket, dst_key, copy_headers, options)
+                .await?;
+            return Ok(true);
+        }
+
+        // Parts don't carry metadata over, the upload is started with it instead
+        let (content_type, metadata) = match options.metadata_directive {
+            MetadataDirective::Copy => source_metadata(source),
+            MetadataDirective::Replace => replacement_metadata(options)?,
+        };
+        let upload_id = self
+            .initiate_multipart_upload(dst_bucket, dst_key, content_type, metadata)
+            .await?;
+
+        let result = self
+            .copy_parts(dst_bucket, dst_key, &upload_id, size, copy_headers)
+            .await;
+        if result.is_err()
+            && let Err(e) = self
+                .abort_multipart_upload(dst_bucket, dst_key, &upload_id)
+                .await
+        {
+            warn!("Failed to abort upload {}: {e:#}", upload_id.yellow());
+        }
+        result.map(|()| false)
+    }
+
+    // Copies an object of up to 5 GiB with a single PUT
+    async fn copy_single(
+        &self,
+        dst_bucket: &str,
+        dst_key: &str,
+        mut headers: HeaderMap,
+        options: &CopyOptions,
+    ) -> Result<()> {
+        headers.insert(
+            "x-obs-metadata-directive",
+            HeaderValue::from_static(options.metadata_directive.as_header()),
+        );
+        let mut content_type = None;
+        if options.metadata_directive == MetadataDirective::Replace {
+            let (replaced_type, metadata) = replacement_metadata(options)?;
+            content_type = replaced_type;
+            headers.extend(metadata);
+        }
+
+        let url = self.endpoint().url(Some(dst_bucket), dst_key, "");
+        let canonical_resource = endpoint::canonical_resource(Some(dst_bucket), dst_key, "");
+
+        let request = ObsRequest {
+            method: Method::PUT,
+            url: &url,
+            body: Body::Text("".to_string()),
+            content_type,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers,
+        };
+
+        let response = self.generate_request(request).await?;
+        let body = ensure_success(response, "Copy object")
+            .await?
+            .text()
+            .await
+            .context("Failed to read response body")?;
+        copy_result_etag(&body).map(drop)
+    }
+
+    // Copies `size` bytes in ranges with Upload Part - Copy and completes the upload
+    async fn copy_parts(
+        &self,
+        dst_bucket: &str,
+        dst_key: &str,
+        upload_id: &str,
+        size: u64,
+        copy_headers: HeaderMap,
+    ) -> Result<()> {
+        let part_size = part_size_for(size, COPY_PART_SIZE)?;
+        info!(
+            "Copying {} in {} parts",
+            bytesize::ByteSize(size),
+            size.div_ceil(part_size)
+        );
+        let bar = transfer_bar(size, "");
+
+        let ranges = (0..size.div_ceil(part_size)).map(|i| {
+            let start = i * part_size;
+            (i as u32 + 1, start, (start + part_size).min(size) - 1)
+        });
+        let mut parts = stream::iter(ranges)
+            .map(|(part_number, start, end)| {
+                let mut headers = copy_headers.clone();
+                let bar = &bar;
+                async move {
+                    headers.insert(
+                        "x-obs-copy-source-range",
+                        HeaderValue::from_str(&format!("bytes={start}-{end}"))?,
+                    );
+                    let etag = self
+                        .upload_part_copy(dst_bucket, dst_key, upload_id, part_number, headers)
+                        .await?;
+                    bar.inc(end - start + 1);
+                    Ok::<_, anyhow::Error>(Part { part_number, etag })
+                }
+            })
+            .buffer_unordered(COPY_CONCURRENCY)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        parts.sort_by_key(|p| p.part_number);
+        self.complete_multipart_upload(dst_bucket, dst_key, upload_id, parts)
+            .await?;
+        bar.finish_with_message("Done");
+        Ok(())
+    }
+
+    // Copies a range of the source (set in `headers`) as a part, returning its ETag
+    async fn upload_part_copy(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        headers: HeaderMap,
+    ) -> Result<String> {
+        let sub_resource = format!("?partNumber={part_number}&uploadId={upload_id}");
+        let url = self.endpoint().url(Some(bucket_name), key, &sub_resource);
+        let canonical_resource =
+            endpoint::canonical_resource(Some(bucket_name), key, &sub_resource);
+
+        let request = ObsRequest {
+            method: Method::PUT,
+            url: &url,
+            body: Body::Text("".to_string()),
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers,
+        };
+
+        let response = self.generate_request(request).await?;
+        let body = ensure_success(response, &format!("Part {part_number} copy"))
+            .await?
+            .text()
+            .await
+            .context("Failed to read response body")?;
+        copy_result_etag(&body)
+    }
+}
+
+// Size of an object from its HEAD response
+fn content_length(headers: &HeaderMap) -> Result<u64> {
+    headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| anyhow!("Missing object size in response"))
+}
+
+// ETag of a copy, copies can fail after OBS already answered 200 so errors are in the body
+fn copy_result_etag(body: &str) -> Result<String> {
+    xml_text(body, "ETag").ok_or_else(|| {
+        let code = xml_text(body, "Code").unwrap_or_else(|| "UnknownError".to_string());
+        let message = xml_text(body, "Message").unwrap_or_default();
+        anyhow!("Copy failed: {code}: {message}")
+    })
+}
+
+// Content type and metadata headers of the source, to start a multipart copy with
+fn source_metadata(source: &HeaderMap) -> (Option<ContentType>, HeaderMap) {
+    let content_type = source
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|mime| ContentType::Other(mime.to_string()));
+
+    let metadata = source
+        .iter()
+        .filter(|(name, _)| {
+            name.as_str().starts_with("x-obs-meta-") || METADATA_HEADERS.contains(name)
+        })
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    (content_type, metadata)
+}
+
+// Content type and metadata headers given in the options
+fn replacement_metadata(options: &CopyOptions) -> Result<(Option<ContentType>, HeaderMap)> {
+    let content_type = options.content_type.clone().map(ContentType::Other);
+
+    Ok((content_type, metadata_headers(&options.metadata)?))
+}
@@ -0,0 +1,150 @@
+use crate::endpoint;
+use crate::obs::{Body, ContentType, ListObjectsOptions, ObsClient, ObsRequest, ensure_success};
+use crate::walk::build_globset;
+use crate::xml::{DeleteError, DeleteObjects, ObjectToDelete};
+use crate::xml_to_struct_vec;
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine as _, engine::general_purpose};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use log::debug;
+use quick_xml::se::to_string;
+use reqwest::Method;
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+
+// Most keys a single Delete Multiple Objects request accepts
+const MAX_KEYS_PER_DELETE: usize = 1000;
+// Batches sent at the same time
+const DELETE_CONCURRENCY: usize = 4;
+
+impl ObsClient {
+    /// Deletes any number of objects, up to 1000 per request.
+    ///
+    /// With `quiet`, OBS only reports the keys it failed to delete, which keeps responses small.
+    /// Returns the outcome of every key, in the same order as `keys`.
+    pub async fn delete_objects(
+        &self,
+        bucket_name: &str,
+        keys: Vec<String>,
+        quiet: bool,
+    ) -> Vec<(String, Result<()>)> {
+        let batches = keys
+            .chunks(MAX_KEYS_PER_DELETE)
+            .map(<[String]>::to_vec)
+            .collect::<Vec<_>>();
+
+        stream::iter(batches)
+            .map(|batch| async move {
+                match self.delete_batch(bucket_name, &batch, quiet).await {
+                    Ok(mut failures) => batch
+                        .into_iter()
+                        .map(|key| {
+                            let result = match failures.remove(&key) {
+                                Some(e) => Err(e),
+                                None => Ok(()),
+                            };
+                            (key, result)
+                        })
+                        .collect::<Vec<_>>(),
+                    // The whole request failed, so did every key in it
+                    Err(e) => batch
+                        .into_iter()
+                        .map(|key| (key, Err(anyhow!("{e:#}"))))
+                        .collect(),
+                }
+            })
+            .buffered(DELETE_CONCURRENCY)
+            .flat_map(stream::iter)
+            .collect()
+            .await
+    }
+
+    /// Deletes every object under `prefix` whose key matches one of `globs`, all of them if
+    /// `globs` is empty.
+    ///
+    /// Globs match whole keys, `*` stays within a "folder" and `**` crosses them (e.g.
+    /// `logs/**/*.gz`). The listing is deleted a page at a time, so any number of objects can be
+    /// cleared. Returns the outcome of every matching key.
+    pub async fn delete_matching(
+        &self,
+        bucket_name: &str,
+        prefix: &str,
+        globs: &[String],
+        quiet: bool,
+    ) -> Result<Vec<(String, Result<()>)>> {
+        let globs = build_globset(globs)?;
+        let options = ListObjectsOptions {
+            prefix: Some(prefix.to_string()).filter(|p| !p.is_empty()),
+            ..ListObjectsOptions::default()
+        };
+
+        let mut results = Vec::new();
+        let mut pages = std::pin::pin!(self.list_objects_pages(bucket_name, options, None));
+        while let Some(page) = pages.try_next().await? {
+            let keys = page
+                .objects
+                .into_iter()
+                .map(|object| object.key)
+                .filter(|key| globs.as_ref().is_none_or(|set| set.is_match(key)))
+                .collect::<Vec<_>>();
+            debug!("Deleting {} matching keys of a listing page", keys.len());
+            results.extend(self.delete_objects(bucket_name, keys, quiet).await);
+        }
+        Ok(results)
+    }
+
+    // Sends a single Delete Multiple Objects request, returning the keys that failed
+    async fn delete_batch(
+        &self,
+        bucket_name: &str,
+        keys: &[String],
+        quiet: bool,
+    ) -> Result<HashMap<String, anyhow::Error>> {
+        if keys.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let delete_xml = to_string(&DeleteObjects {
+            quiet,
+            objects: keys
+                .iter()
+                .map(|key| ObjectToDelete { key: key.clone() })
+                .collect(),
+        })?;
+        // OBS refuses batch deletes without it
+        let content_md5 =
+            general_purpose::STANDARD.encode(md5::compute(delete_xml.as_bytes()).as_ref());
+
+        let url = self.endpoint().url(Some(bucket_name), "", "?delete");
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), "", "?delete");
+
+        let request = ObsRequest {
+            method: Method::POST,
+            url: &url,
+            body: Body::Binary(delete_xml.into()),
+            content_type: Some(ContentType::ApplicationXml),
+            content_md5: &content_md5,
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        let raw_xml = ensure_success(response, "Delete objects")
+            .await?
+            .text()
+            .await
+            .context("Failed to read response body")?;
+
+        let errors = xml_to_struct_vec!(
+            DeleteError => "Error" in &raw_xml, {
+                Key => key,
+                Code => code,
+                Message => message,
+            }
+        )?;
+        Ok(errors
+            .into_iter()
+            .map(|e| (e.key, anyhow!("{}: {}", e.code, e.message)))
+            .collect())
+    }
+}
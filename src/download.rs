@@ -0,0 +1,538 @@
+use crate::endpoint;
+use crate::error::ObsError;
+use crate::obs::{Body, ObsClient, ObsRequest, ensure_success, write_json_atomic};
+use anyhow::{Context, Result, anyhow};
+use colored::Colorize;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use indicatif::ProgressBar;
+use log::{debug, info, warn};
+use reqwest::header::{CONTENT_RANGE, ETAG, HeaderMap, HeaderValue, IF_MATCH, RANGE};
+use reqwest::{Method, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Size of the ranges large objects are downloaded in by default.
+pub const DEFAULT_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Settings for downloading objects.
+#[derive(Clone, Debug)]
+pub struct DownloadOptions {
+    /// Objects larger than this are downloaded as concurrent ranges of this size
+    pub chunk_size: u64,
+    /// Ranges of a single object downloaded at the same time
+    pub concurrency: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            concurrency: 8,
+        }
+    }
+}
+
+impl ObsClient {
+    // Downloads a single object to a local path, creating its parent directories
+    pub(crate) async fn download_to(
+        &self,
+        bucket_name: &str,
+        object_path: &str,
+        local_path: &Path,
+        options: &DownloadOptions,
+        bar: &ProgressBar,
+        total: Option<&ProgressBar>,
+    ) -> Result<()> {
+        // Create directories for output path
+        if let Some(parent) = local_path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+
+        // The object only shows up at `local_path` once it's complete
+        let temp_path = temp_download_path(local_path);
+        let result = self
+            .fetch_object(bucket_name, object_path, &temp_path, options, bar, total)
+            .await;
+        // Partial downloads with a state file are kept to be resumed
+        if result.is_err() && !download_state_path(&temp_path).exists() {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+        }
+        result?;
+
+        tokio::fs::rename(&temp_path, local_path)
+            .await
+            .with_context(|| format!("Failed to move download to {}", local_path.display()))?;
+        Ok(())
+    }
+
+    /// Writes an object to `writer` (e.g. stdout) as it downloads, returning its size.
+    pub async fn download_to_writer<W: AsyncWrite + Unpin>(
+        &self,
+        bucket_name: &str,
+        object_path: &str,
+        writer: &mut W,
+    ) -> Result<u64> {
+        let mut response = self
+            .get_object(bucket_name, object_path, None, None)
+            .await?;
+
+        let mut written = 0;
+        while let Some(chunk) = response.chunk().await? {
+            writer
+                .write_all(&chunk)
+                .await
+                .context("Failed to write downloaded content")?;
+            written += chunk.len() as u64;
+        }
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    // Writes an object to `path`, as concurrent ranges if it's larger than a chunk
+    //
+    // Ranged downloads keep a state file next to `path` listing the ranges written so far,
+    // an interrupted download continues from it unless the object changed in the meantime.
+    async fn fetch_object(
+        &self,
+        bucket_name: &str,
+        object_path: &str,
+        path: &Path,
+        options: &DownloadOptions,
+        bar: &ProgressBar,
+        total: Option<&ProgressBar>,
+    ) -> Result<()> {
+        let state_path = download_state_path(path);
+        if let Some(state) = DownloadState::load(&state_path).filter(|_| path.exists()) {
+            info!(
+                "Resuming download of {}, {} of {} bytes already downloaded",
+                object_path.cyan(),
+                state.completed_bytes(),
+                state.size
+            );
+            let result = self
+                .fetch_ranges(
+                    bucket_name,
+                    object_path,
+                    path,
+                    state,
+                    None,
+                    options,
+                    bar,
+                    total,
+                )
+                .await;
+            match result {
+                Err(e) if is_precondition_failed(&e) => {
+                    warn!(
+                        "{} changed since its download was interrupted, starting over",
+                        object_path.yellow()
+                    );
+                    bar.reset();
+                    let _ = fs::remove_file(&state_path);
+                }
+                result => return result,
+            }
+        }
+
+        // The first range tells the object size, small objects are done after it
+        let chunk_size = options.chunk_size.max(1);
+        let first_range = format!("bytes=0-{}", chunk_size - 1);
+        let response = self
+            .get_object(bucket_name, object_path, Some(&first_range), None)
+            .await?;
+        let size = match response.status() {
+            StatusCode::PARTIAL_CONTENT => content_range_size(&response)?,
+            // Whole object, the range was ignored
+            _ => return write_body(response, path, bar, total).await,
+        };
+        if size <= chunk_size {
+            return write_body(response, path, bar, total).await;
+        }
+
+        // Without an ETag, a resumed download couldn't tell if the object changed
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(String::from);
+        debug!(
+            "Downloading {object_path} ({size} bytes) in ranges of {chunk_size} bytes, ETag {etag:?}"
+        );
+
+        let file = tokio::fs::File::create(path)
+            .await
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        file.set_len(size)
+            .await
+            .with_context(|| format!("Failed to allocate {size} bytes for {}", path.display()))?;
+
+        let state = DownloadState {
+            etag,
+            size,
+            chunk_size,
+            completed: BTreeSet::new(),
+        };
+        self.fetch_ranges(
+            bucket_name,
+            object_path,
+            path,
+            state,
+            Some(response),
+            options,
+            bar,
+            total,
+        )
+        .await
+    }
+
+    // Downloads the ranges missing from `state` into the pre-allocated file at `path`
+    //
+    // `first` is the response of a request for the first range, if one was already sent.
+    #[allow(clippy::too_many_arguments)]
+    async fn fetch_ranges(
+        &self,
+        bucket_name: &str,
+        object_path: &str,
+        path: &Path,
+        state: DownloadState,
+        first: Option<Response>,
+        options: &DownloadOptions,
+        bar: &ProgressBar,
+        total: Option<&ProgressBar>,
+    ) -> Result<()> {
+        // Ranges are written with positioned writes, on the blocking pool
+        let file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let file = Arc::new(file.into_std().await);
+        let state_path = download_state_path(path);
+
+        bar.set_length(state.size);
+        bar.inc(state.completed_bytes());
+        if let Some(total) = total {
+            total.inc(state.completed_bytes());
+        }
+
+        let (size, chunk_size, etag) = (state.size, state.chunk_size, state.etag.clone());
+        let missing = (0..size)
+            .step_by(chunk_size as usize)
+            .filter(|start| !state.completed.contains(start))
+            .filter(|&start| start != 0 || first.is_none())
+            .collect::<Vec<_>>();
+        let state = Mutex::new(state);
+
+        // Records a finished range, so an interruption doesn't lose it
+        let complete = |start: u64| -> Result<()> {
+            let mut state = state.lock().expect("Download state lock poisoned");
+            state.completed.insert(start);
+            if state.etag.is_some() {
+                state.save(&state_path)?;
+            }
+            Ok(())
+        };
+
+        let max_attempts = self.retry_policy.max_attempts.max(1);
+        // Writes a range, from `response` if it was already requested, with new requests if the
+        // connection drops while its body streams
+        let fetch_range = |start: u64, mut response: Option<Response>| {
+            let len = chunk_size.min(size - start);
+            let file = &file;
+            let etag = etag.as_deref();
+            async move {
+                let mut attempt = 1;
+                loop {
+                    let response = match response.take() {
+                        Some(response) => response,
+                        None => {
+                            let range = format!("bytes={start}-{}", start + len - 1);
+                            let response = self
+                                .get_object(bucket_name, object_path, Some(&range), etag)
+                                .await?;
+                            if response.status() != StatusCode::PARTIAL_CONTENT {
+                                return Err(anyhow!("OBS didn't return the range {range}"));
+                            }
+                            response
+                        }
+                    };
+
+                    match write_range(response, file, path, start, len, bar, total).await {
+                        Err(e) if attempt < max_attempts && e.is::<RangeInterrupted>() => {
+                            let delay = self.retry_policy.backoff(attempt);
+                            debug!(
+                                "Retrying range at byte {start} of {object_path} in {delay:?} (attempt {}/{max_attempts}): {e:#}",
+                                attempt + 1
+                            );
+                            tokio::time::sleep(delay).await;
+                            attempt += 1;
+                        }
+                        result => return result,
+                    }
+                }
+            }
+        };
+
+        let first = async {
+            if let Some(response) = first {
+                fetch_range(0, Some(response)).await?;
+                complete(0)?;
+            }
+            Ok(())
+        };
+        let rest = stream::iter(missing)
+            .map(|start| async move {
+                fetch_range(start, None).await?;
+                complete(start)
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .try_collect::<()>();
+        futures::try_join!(first, rest)?;
+
+        tokio::task::spawn_blocking(move || file.sync_all()).await??;
+        if state_path.exists() {
+            tokio::fs::remove_file(&state_path)
+                .await
+                .with_context(|| format!("Failed to remove {}", state_path.display()))?;
+        }
+        Ok(())
+    }
+
+    // Sends a GET for an object, optionally of a byte range of a specific version (ETag)
+    async fn get_object(
+        &self,
+        bucket_name: &str,
+        object_path: &str,
+        range: Option<&str>,
+        if_match: Option<&str>,
+    ) -> Result<Response> {
+        let url = self.endpoint().url(Some(bucket_name), object_path, "");
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), object_path, "");
+
+        let mut headers = HeaderMap::new();
+        if let Some(range) = range {
+            headers.insert(RANGE, HeaderValue::from_str(range)?);
+        }
+        if let Some(etag) = if_match {
+            headers.insert(IF_MATCH, HeaderValue::from_str(etag)?);
+        }
+
+        let request = ObsRequest {
+            method: Method::GET,
+            url: &url,
+            body: Body::Text("".to_string()),
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers,
+        };
+
+        let response = self.generate_request(request).await?;
+        // Empty objects have no first byte to start a range from
+        if range.is_some() && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Box::pin(self.get_object(bucket_name, object_path, None, if_match)).await;
+        }
+        ensure_success(response, "Download object").await
+    }
+}
+
+// Suffix of partial downloads, their state files add `.json` to it
+const PARTIAL_DOWNLOAD_SUFFIX: &str = ".obsctl-download";
+
+// Hidden file next to the destination, so renaming it in place is atomic
+fn temp_download_path(local_path: &Path) -> PathBuf {
+    let file_name = local_path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    local_path.with_file_name(format!(".{file_name}{PARTIAL_DOWNLOAD_SUFFIX}"))
+}
+
+/// Whether a file is a partial download or its state file, which must never be uploaded or
+/// deleted by a sync while the download can still be resumed.
+pub(crate) fn is_partial_download(file_name: &str) -> bool {
+    let name = file_name.strip_suffix(".json").unwrap_or(file_name);
+    name.starts_with('.') && name.ends_with(PARTIAL_DOWNLOAD_SUFFIX)
+}
+
+/// Ranges of an interrupted download, saved next to the partial file.
+#[derive(Serialize, Deserialize)]
+struct DownloadState {
+    // Resumed ranges are requested with If-Match, so they must be of this version
+    etag: Option<String>,
+    size: u64,
+    chunk_size: u64,
+    // Start offsets of the ranges already written
+    completed: BTreeSet<u64>,
+}
+
+impl DownloadState {
+    fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content)
+            .inspect_err(|e| warn!("Ignoring invalid download state {}: {e}", path.display()))
+            .ok()
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        write_json_atomic(path, self)
+    }
+
+    fn completed_bytes(&self) -> u64 {
+        self.completed
+            .iter()
+            .map(|&start| self.chunk_size.min(self.size.saturating_sub(start)))
+            .sum()
+    }
+}
+
+fn download_state_path(partial_path: &Path) -> PathBuf {
+    let mut path = partial_path.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+// OBS answers 412 when the object no longer matches the If-Match ETag
+fn is_precondition_failed(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ObsError>()
+        .is_some_and(|e| e.status == StatusCode::PRECONDITION_FAILED)
+}
+
+// Total object size from a `Content-Range: bytes 0-99/1234` header
+fn content_range_size(response: &Response) -> Result<u64> {
+    response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|range| range.to_str().ok())
+        .and_then(|range| range.rsplit_once('/'))
+        .and_then(|(_, size)| size.parse().ok())
+        .ok_or_else(|| anyhow!("Missing or invalid Content-Range in ranged download"))
+}
+
+// Streams a response body into a file, responses without Content-Length included
+async fn write_body(
+    mut response: Response,
+    path: &Path,
+    bar: &ProgressBar,
+    total: Option<&ProgressBar>,
+) -> Result<()> {
+    let expected_size = response.content_length();
+    if let Some(size) = expected_size {
+        bar.set_length(size);
+    }
+
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+
+    let mut written = 0;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)
+            .await
+            .with_context(|| format!("Failed to write to {}", path.display()))?;
+        written += chunk.len() as u64;
+
+        if expected_size.is_none() {
+            bar.inc_length(chunk.len() as u64);
+        }
+        bar.inc(chunk.len() as u64);
+        if let Some(total) = total {
+            total.inc(chunk.len() as u64);
+        }
+    }
+    file.sync_all().await?;
+
+    if let Some(size) = expected_size.filter(|&size| size != written) {
+        return Err(anyhow!(
+            "Download ended after {written} of {size} bytes, try again"
+        ));
+    }
+    Ok(())
+}
+
+// Writes a ranged response at its offset of a pre-allocated file
+async fn write_range(
+    mut response: Response,
+    file: &Arc<fs::File>,
+    path: &Path,
+    start: u64,
+    len: u64,
+    bar: &ProgressBar,
+    total: Option<&ProgressBar>,
+) -> Result<()> {
+    let mut offset = start;
+    let result = async {
+        while let Some(chunk) = response.chunk().await.context(RangeInterrupted { start })? {
+            let written = chunk.len() as u64;
+            let file = Arc::clone(file);
+            tokio::task::spawn_blocking(move || write_at(&file, &chunk, offset))
+                .await?
+                .with_context(|| format!("Failed to write to {}", path.display()))?;
+            offset += written;
+
+            bar.inc(written);
+            if let Some(total) = total {
+                total.inc(written);
+            }
+        }
+
+        if offset - start != len {
+            return Err(
+                anyhow!("Range ended after {} of {len} bytes", offset - start)
+                    .context(RangeInterrupted { start }),
+            );
+        }
+        Ok(())
+    }
+    .await;
+
+    // A retried range is written again from its start
+    if result.is_err() {
+        bar.dec(offset - start);
+        if let Some(total) = total {
+            total.dec(offset - start);
+        }
+    }
+    result
+}
+
+// A range whose body was cut short, worth requesting again
+#[derive(Debug)]
+struct RangeInterrupted {
+    start: u64,
+}
+
+impl fmt::Display for RangeInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Download of the range at byte {} was interrupted",
+            self.start
+        )
+    }
+}
+
+#[cfg(unix)]
+fn write_at(file: &fs::File, buf: &[u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn write_at(file: &fs::File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        let written = file.seek_write(buf, offset)?;
+        buf = &buf[written..];
+        offset += written as u64;
+    }
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use anyhow::{Context, Result, anyhow, bail};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use std::fmt;
+use std::str::FromStr;
+
+// Unreserved characters (RFC 3986) are the only ones sent as is in query values
+const QUERY_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+// Keys keep their `/` so "folders" stay readable paths
+const KEY: &AsciiSet = &QUERY_VALUE.remove(b'/');
+
+/// Percent-encodes an object key for URLs and signing, e.g. `a b/ü#1` as `a%20b/%C3%BC%231`.
+pub fn encode_key(key: &str) -> String {
+    utf8_percent_encode(key, KEY).to_string()
+}
+
+/// Percent-encodes a query parameter value, including any `/`, `&` or `=`.
+pub fn encode_query_value(value: &str) -> String {
+    utf8_percent_encode(value, QUERY_VALUE).to_string()
+}
+
+/// Decodes a value of a listing requested with `encoding-type=url`, where `+` is a space.
+pub fn decode_url(value: &str) -> Result<String> {
+    let value = value.replace('+', " ");
+    percent_decode_str(&value)
+        .decode_utf8()
+        .map(|decoded| decoded.into_owned())
+        .with_context(|| format!("Invalid URL-encoded value '{value}' in listing"))
+}
+
+/// How the bucket name is placed in request URLs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressingStyle {
+    /// `https://{bucket}.{host}/{key}`, the OBS default
+    #[default]
+    VirtualHosted,
+    /// `https://{host}/{bucket}/{key}`, for endpoints without wildcard DNS
+    Path,
+}
+
+impl FromStr for AddressingStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "virtual" | "virtual-hosted" => Ok(AddressingStyle::VirtualHosted),
+            "path" => Ok(AddressingStyle::Path),
+            other => Err(anyhow!(
+                "Unknown addressing style '{other}', expected 'virtual' or 'path'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for AddressingStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressingStyle::VirtualHosted => write!(f, "virtual"),
+            AddressingStyle::Path => write!(f, "path"),
+        }
+    }
+}
+
+/// Where requests are sent to, every OBS URL is built from this.
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    scheme: String,
+    host: String,
+    style: AddressingStyle,
+}
+
+impl Endpoint {
+    /// The public Huawei Cloud endpoint of a region.
+    pub fn for_region(region: &str) -> Self {
+        Self {
+            scheme: "https".to_string(),
+            host: format!("obs.{region}.myhuaweicloud.com"),
+            style: AddressingStyle::VirtualHosted,
+        }
+    }
+
+    /// Parses `host[:port]` or `scheme://host[:port]`, defaulting to HTTPS.
+    pub fn parse(endpoint: &str) -> Result<Self> {
+        let (scheme, rest) = match endpoint.split_once("://") {
+            Some((scheme, rest)) => (scheme.to_lowercase(), rest),
+            None => ("https".to_string(), endpoint),
+        };
+
+        if scheme != "http" && scheme != "https" {
+            bail!("Unsupported endpoint scheme '{scheme}', expected 'http' or 'https'");
+        }
+
+        let host = rest.trim_end_matches('/');
+        if host.is_empty() || host.contains('/') {
+            bail!("Invalid endpoint '{endpoint}', expected a host name such as obs.example.com");
+        }
+
+        Ok(Self {
+            scheme,
+            host: host.to_string(),
+            style: AddressingStyle::VirtualHosted,
+        })
+    }
+
+    pub fn with_addressing_style(mut self, style: AddressingStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Switches to unencrypted HTTP, only meant for legacy setups.
+    pub fn with_plain_http(mut self) -> Self {
+        self.scheme = "http".to_string();
+        self
+    }
+
+    pub fn is_https(&self) -> bool {
+        self.scheme == "https"
+    }
+
+    pub fn scheme(&self) -> &str {
+        &self.scheme
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn addressing_style(&self) -> AddressingStyle {
+        self.style
+    }
+
+    /// Builds the URL of a bucket, an object (non-empty `key`) or the service itself (no bucket).
+    ///
+    /// `key` is percent-encoded here, but `query` is appended as is, so it must be empty or
+    /// start with `?` and have its values encoded already.
+    pub fn url(&self, bucket: Option<&str>, key: &str, query: &str) -> String {
+        let Endpoint {
+            scheme,
+            host,
+            style,
+        } = self;
+        let key = encode_key(key);
+
+        match (bucket, style) {
+            (None, _) => format!("{scheme}://{host}/{query}"),
+            (Some(bucket), AddressingStyle::VirtualHosted) => {
+                format!("{scheme}://{bucket}.{host}/{key}{query}")
+            }
+            (Some(bucket), AddressingStyle::Path) => {
+                format!("{scheme}://{host}/{bucket}/{key}{query}")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.host)
+    }
+}
+
+/// Resource path used when signing, it doesn't depend on the addressing style.
+///
+/// The key is percent-encoded the same way as in [`Endpoint::url`], OBS signs the path it receives.
+pub fn canonical_resource(bucket: Option<&str>, key: &str, sub_resource: &str) -> String {
+    match bucket {
+        Some(bucket) => format!("/{bucket}/{}{sub_resource}", encode_key(key)),
+        None => "/".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keys that break URLs or signatures unless encoded
+    const TRICKY_KEYS: [&str; 7] = [
+        "a b.txt",
+        "issue#12",
+        "what?.md",
+        "c++/main.cpp",
+        "100%.png",
+        "logs/2025/app.log",
+        "relatórios/año 2025.pdf",
+    ];
+
+    #[test]
+    fn encode_key_keeps_slashes() {
+        assert_eq!(encode_key("a b.txt"), "a%20b.txt");
+        assert_eq!(encode_key("issue#12"), "issue%2312");
+        assert_eq!(encode_key("what?.md"), "what%3F.md");
+        assert_eq!(encode_key("c++/main.cpp"), "c%2B%2B/main.cpp");
+        assert_eq!(encode_key("100%.png"), "100%25.png");
+        assert_eq!(encode_key("logs/2025/app.log"), "logs/2025/app.log");
+        assert_eq!(
+            encode_key("relatórios/año 2025.pdf"),
+            "relat%C3%B3rios/a%C3%B1o%202025.pdf"
+        );
+        assert_eq!(encode_key("a-b_c.d~e"), "a-b_c.d~e");
+    }
+
+    #[test]
+    fn encode_query_value_encodes_separators() {
+        assert_eq!(encode_query_value("logs/2025/"), "logs%2F2025%2F");
+        assert_eq!(encode_query_value("a&b=c"), "a%26b%3Dc");
+        assert_eq!(encode_query_value("a b+c#d?e%f"), "a%20b%2Bc%23d%3Fe%25f");
+        assert_eq!(
+            encode_query_value("relatórios/año 2025.pdf"),
+            "relat%C3%B3rios%2Fa%C3%B1o%202025.pdf"
+        );
+    }
+
+    #[test]
+    fn decode_url_handles_plus_and_escapes() {
+        assert_eq!(decode_url("a+b").unwrap(), "a b");
+        assert_eq!(decode_url("c%2B%2B").unwrap(), "c++");
+        assert_eq!(decode_url("100%25.png").unwrap(), "100%.png");
+        assert_eq!(
+            decode_url("relat%C3%B3rios/a%C3%B1o+2025.pdf").unwrap(),
+            "relatórios/año 2025.pdf"
+        );
+        assert!(decode_url("%FF").is_err());
+    }
+
+    #[test]
+    fn decode_url_reverses_encoding() {
+        for key in TRICKY_KEYS {
+            assert_eq!(decode_url(&encode_key(key)).unwrap(), key);
+            assert_eq!(decode_url(&encode_query_value(key)).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn url_encodes_keys_in_both_styles() {
+        let endpoint = Endpoint::parse("obs.example.com").unwrap();
+        assert_eq!(
+            endpoint.url(Some("bkt"), "relatórios/año 2025.pdf", "?uploads"),
+            "https://bkt.obs.example.com/relat%C3%B3rios/a%C3%B1o%202025.pdf?uploads"
+        );
+        assert_eq!(
+            endpoint.url(Some("bkt"), "what?#1+%", ""),
+            "https://bkt.obs.example.com/what%3F%231%2B%25"
+        );
+        assert_eq!(endpoint.url(None, "", ""), "https://obs.example.com/");
+
+        let endpoint = endpoint.with_addressing_style(AddressingStyle::Path);
+        assert_eq!(
+            endpoint.url(Some("bkt"), "relatórios/año 2025.pdf", "?uploads"),
+            "https://obs.example.com/bkt/relat%C3%B3rios/a%C3%B1o%202025.pdf?uploads"
+        );
+        assert_eq!(
+            endpoint.url(Some("bkt"), "what?#1+%", ""),
+            "https://obs.example.com/bkt/what%3F%231%2B%25"
+        );
+    }
+
+    #[test]
+    fn canonical_resource_matches_url_path() {
+        assert_eq!(
+            canonical_resource(Some("bkt"), "a b/c+d?#%.txt", "?acl"),
+            "/bkt/a%20b/c%2Bd%3F%23%25.txt?acl"
+        );
+        assert_eq!(canonical_resource(Some("bkt"), "", ""), "/bkt/");
+        assert_eq!(canonical_resource(None, "ignored", ""), "/");
+
+        // Path-style URLs carry the same path OBS signs
+        let endpoint = Endpoint::parse("http://localhost:9000")
+            .unwrap()
+            .with_addressing_style(AddressingStyle::Path);
+        for key in TRICKY_KEYS {
+            let url = endpoint.url(Some("bkt"), key, "");
+            assert_eq!(
+                url.strip_prefix("http://localhost:9000").unwrap(),
+                canonical_resource(Some("bkt"), key, "")
+            );
+        }
+    }
+}
@@ -1,8 +1,7 @@
-use anyhow::Result;
 use colored::*;
-use log::{error, info, warn};
-use reqwest::StatusCode;
-use tabled::{Table, Tabled, settings::style::Style};
+use log::error;
+use reqwest::{Response, StatusCode};
+use std::fmt;
 
 /// Logs an `anyhow::Error` and its causal chain.
 pub fn log_error_chain(err: anyhow::Error) {
@@ -15,38 +14,239 @@ pub fn log_error_chain(err: anyhow::Error) {
     error!("{msg}");
 }
 
-/// Logs the status and body of an API response.
-pub async fn log_api_response<T: Tabled>(
-    status: StatusCode,
-    parsed: Option<Vec<T>>,
-    raw_body: &str,
-) -> Result<()> {
-    let display_body = if raw_body.trim().is_empty() {
-        "No text in response body".bright_blue().to_string()
-    } else if let Some(parsed_data) = parsed {
-        if parsed_data.is_empty() {
-            "No entries in response table".bright_yellow().to_string()
-        } else {
-            let mut table = Table::new(parsed_data);
-            table.with(Style::rounded());
-            format!("{table}")
-        }
-    } else {
-        raw_body.to_string()
-    };
-
-    let msg = format!(
-        "{} {}\n{}",
-        "Result:".bright_green().bold(),
-        status,
-        display_body
-    );
-
-    if status.is_success() {
-        info!("{msg}");
-    } else {
-        warn!("{msg}");
-    }
-
-    Ok(())
+/// Well-known OBS error codes, anything else is `Other`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObsErrorKind {
+    NoSuchBucket,
+    NoSuchKey,
+    NoSuchUpload,
+    AccessDenied,
+    SignatureDoesNotMatch,
+    InvalidAccessKeyId,
+    RequestTimeTooSkewed,
+    BucketNotEmpty,
+    BucketAlreadyExists,
+    BucketAlreadyOwnedByYou,
+    InvalidBucketName,
+    TooManyBuckets,
+    EntityTooLarge,
+    EntityTooSmall,
+    InvalidPart,
+    InvalidPartOrder,
+    PreconditionFailed,
+    InvalidRange,
+    SlowDown,
+    ServiceUnavailable,
+    InternalError,
+    Other,
+}
+
+impl ObsErrorKind {
+    fn from_code(code: &str) -> Self {
+        match code {
+            "NoSuchBucket" => ObsErrorKind::NoSuchBucket,
+            "NoSuchKey" => ObsErrorKind::NoSuchKey,
+            "NoSuchUpload" => ObsErrorKind::NoSuchUpload,
+            "AccessDenied" => ObsErrorKind::AccessDenied,
+            "SignatureDoesNotMatch" => ObsErrorKind::SignatureDoesNotMatch,
+            "InvalidAccessKeyId" => ObsErrorKind::InvalidAccessKeyId,
+            "RequestTimeTooSkewed" => ObsErrorKind::RequestTimeTooSkewed,
+            "BucketNotEmpty" => ObsErrorKind::BucketNotEmpty,
+            "BucketAlreadyExists" => ObsErrorKind::BucketAlreadyExists,
+            "BucketAlreadyOwnedByYou" => ObsErrorKind::BucketAlreadyOwnedByYou,
+            "InvalidBucketName" => ObsErrorKind::InvalidBucketName,
+            "TooManyBuckets" => ObsErrorKind::TooManyBuckets,
+            "EntityTooLarge" => ObsErrorKind::EntityTooLarge,
+            "EntityTooSmall" => ObsErrorKind::EntityTooSmall,
+            "InvalidPart" => ObsErrorKind::InvalidPart,
+            "InvalidPartOrder" => ObsErrorKind::InvalidPartOrder,
+            "PreconditionFailed" => ObsErrorKind::PreconditionFailed,
+            "InvalidRange" => ObsErrorKind::InvalidRange,
+            "SlowDown" => ObsErrorKind::SlowDown,
+            "ServiceUnavailable" => ObsErrorKind::ServiceUnavailable,
+            "InternalError" => ObsErrorKind::InternalError,
+            _ => ObsErrorKind::Other,
+        }
+    }
+
+    /// Guesses the kind of an error response without a body, e.g. for HEAD requests.
+    fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::FORBIDDEN => ObsErrorKind::AccessDenied,
+            StatusCode::PRECONDITION_FAILED => ObsErrorKind::PreconditionFailed,
+            StatusCode::RANGE_NOT_SATISFIABLE => ObsErrorKind::InvalidRange,
+            StatusCode::SERVICE_UNAVAILABLE => ObsErrorKind::ServiceUnavailable,
+            StatusCode::INTERNAL_SERVER_ERROR => ObsErrorKind::InternalError,
+            _ => ObsErrorKind::Other,
+        }
+    }
+
+    /// What the user can do about the error, if there's anything useful to say.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            ObsErrorKind::NoSuchBucket => {
+                Some("Check the bucket name and that it belongs to the selected region")
+            }
+            ObsErrorKind::NoSuchKey => Some("Check the object path, keys are case-sensitive"),
+            ObsErrorKind::NoSuchUpload => {
+                Some("The multipart upload was completed or aborted, start a new upload")
+            }
+            ObsErrorKind::AccessDenied => Some(
+                "Your AK/SK lacks permission for this operation, check the IAM and bucket policies",
+            ),
+            ObsErrorKind::SignatureDoesNotMatch => {
+                Some("The SK doesn't match the AK, check your credentials")
+            }
+            ObsErrorKind::InvalidAccessKeyId => {
+                Some("The AK doesn't exist, check your credentials")
+            }
+            ObsErrorKind::RequestTimeTooSkewed => {
+                Some("Your system clock is off, synchronize it and try again")
+            }
+            ObsErrorKind::BucketNotEmpty => {
+                Some("Delete every object in the bucket before deleting the bucket")
+            }
+            ObsErrorKind::BucketAlreadyExists => {
+                Some("Bucket names are global across all users, choose another name")
+            }
+            ObsErrorKind::BucketAlreadyOwnedByYou => Some("You already own this bucket"),
+            ObsErrorKind::InvalidBucketName => Some(
+                "Bucket names must be 3 to 63 characters of lowercase letters, digits, hyphens and periods",
+            ),
+            ObsErrorKind::TooManyBuckets => {
+                Some("The account bucket quota is reached, delete unused buckets first")
+            }
+            ObsErrorKind::EntityTooLarge => Some("The object or part exceeds the maximum size"),
+            ObsErrorKind::EntityTooSmall => {
+                Some("Every part except the last must be at least 100 KB")
+            }
+            ObsErrorKind::SlowDown | ObsErrorKind::ServiceUnavailable => {
+                Some("OBS is throttling requests, try again later")
+            }
+            ObsErrorKind::InternalError => Some("Temporary server error, try again later"),
+            ObsErrorKind::InvalidPart
+            | ObsErrorKind::InvalidPartOrder
+            | ObsErrorKind::PreconditionFailed
+            | ObsErrorKind::InvalidRange
+            | ObsErrorKind::Other => None,
+        }
+    }
+}
+
+/// Error returned by the OBS API, parsed from its `<Error>` XML body.
+///
+/// Library users can get it back with `anyhow::Error::downcast_ref::<ObsError>()`.
+#[derive(Debug, Clone)]
+pub struct ObsError {
+    pub kind: ObsErrorKind,
+    pub status: StatusCode,
+    pub code: String,
+    pub message: String,
+    pub request_id: Option<String>,
+    pub host_id: Option<String>,
+}
+
+impl ObsError {
+    /// Consumes a failed response and parses its error body.
+    pub async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let header_request_id = response
+            .headers()
+            .get("x-obs-request-id")
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let body = response.text().await.unwrap_or_default();
+
+        let mut err = Self::parse(status, &body);
+        if err.request_id.is_none() {
+            err.request_id = header_request_id;
+        }
+        err
+    }
+
+    /// Parses an error body, falling back on the status when it isn't OBS error XML.
+    pub fn parse(status: StatusCode, body: &str) -> Self {
+        let doc = roxmltree::Document::parse(body).ok();
+        let root = doc
+            .as_ref()
+            .map(|doc| doc.root_element())
+            .filter(|root| root.has_tag_name("Error"));
+
+        let field = |tag: &str| {
+            root.and_then(|root| root.children().find(|n| n.has_tag_name(tag)))
+                .and_then(|n| n.text())
+                .map(String::from)
+        };
+
+        match field("Code") {
+            Some(code) => Self {
+                kind: ObsErrorKind::from_code(&code),
+                status,
+                message: field("Message").unwrap_or_default(),
+                request_id: field("RequestId"),
+                host_id: field("HostId"),
+                code,
+            },
+            None => Self {
+                kind: ObsErrorKind::from_status(status),
+                status,
+                code: status
+                    .canonical_reason()
+                    .unwrap_or("Unknown")
+                    .replace(' ', ""),
+                message: body.trim().to_string(),
+                request_id: None,
+                host_id: None,
+            },
+        }
+    }
+
+    /// Names a 404 without an error body, e.g. of a HEAD request, after what was looked for.
+    pub(crate) fn not_found_as(mut self, kind: ObsErrorKind) -> Self {
+        if self.status == StatusCode::NOT_FOUND && self.kind == ObsErrorKind::Other {
+            self.kind = kind;
+            // Variants are named after their OBS codes
+            self.code = format!("{kind:?}");
+        }
+        self
+    }
+}
+
+impl fmt::Display for ObsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.code, self.status)?;
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        if let Some(hint) = self.kind.hint() {
+            write!(f, "\nHint: {hint}")?;
+        }
+        if let Some(request_id) = &self.request_id {
+            write!(f, "\nRequest ID: {request_id}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ObsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_found_without_body_takes_kind() {
+        let err = ObsError::parse(StatusCode::NOT_FOUND, "").not_found_as(ObsErrorKind::NoSuchKey);
+        assert_eq!(err.kind, ObsErrorKind::NoSuchKey);
+        assert_eq!(err.code, "NoSuchKey");
+    }
+
+    #[test]
+    fn not_found_with_code_keeps_it() {
+        let body = "<Error><Code>NoSuchBucket</Code><Message>gone</Message></Error>";
+        let err =
+            ObsError::parse(StatusCode::NOT_FOUND, body).not_found_as(ObsErrorKind::NoSuchKey);
+        assert_eq!(err.kind, ObsErrorKind::NoSuchBucket);
+        assert_eq!(err.code, "NoSuchBucket");
+    }
 }
@@ -0,0 +1,34 @@
+//! Client library for Huawei Cloud Object Storage Service (OBS).
+//!
+//! The `obsctl` binary is a thin consumer of this crate, but every operation
+//! is also available to other Rust programs through [`ObsClient`].
+
+pub mod auth; // Manages credential loading and validation.
+pub mod buffer; // Upload buffers bounded by a shared memory budget.
+pub mod copy; // Server-side copies and moves of objects.
+pub mod delete; // Batch deletes of many keys, prefixes and globs.
+pub mod download; // Streamed and parallel ranged object downloads.
+pub mod endpoint; // Builds request URLs for a configurable endpoint.
+pub mod error; // Provides error handling and logging utilities.
+pub mod metadata; // Object and bucket metadata from HEAD requests.
+pub mod multipart; // Multipart uploads and their resumable checkpoints.
+pub mod obs; // Contains OBS API interaction logic.
+pub mod region; // Huawei Cloud regions and region name matching.
+pub mod retry; // Retry policy with exponential backoff for failed requests.
+pub mod sync; // Mirrors local directories and bucket prefixes.
+pub mod walk; // Walks local directories for recursive transfers.
+pub mod xml; // Macros for XML-based structs and parsing
+
+pub use crate::buffer::DEFAULT_MEMORY_BUDGET;
+pub use crate::copy::{CopyOptions, MetadataDirective};
+pub use crate::download::{DEFAULT_CHUNK_SIZE, DownloadOptions};
+pub use crate::endpoint::{AddressingStyle, Endpoint};
+pub use crate::metadata::{BucketMetadata, ObjectMetadata};
+pub use crate::multipart::{DEFAULT_PART_CONCURRENCY, DEFAULT_PART_SIZE};
+pub use crate::obs::{
+    Credentials, DEFAULT_MULTIPART_THRESHOLD, ListObjectsOptions, ObjectListPage, ObsClient,
+    ObsClientBuilder, UploadOptions,
+};
+pub use crate::retry::RetryPolicy;
+pub use crate::sync::{SyncDirection, SyncItem, SyncOp};
+pub use crate::walk::{SymlinkPolicy, WalkOptions};
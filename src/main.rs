@@ -1,56 +1,47 @@
-mod auth; // Manages credential loading and validation.
 mod cli; // Defines the command-line interface structure.
 mod config; // Configurations for the CLI
-mod error; // Provides error handling and logging utilities.
-mod obs; // Contains OBS API interaction logic.
-mod xml; // Macros for XML-based structs and parsing
+mod output; // Renders operation results for the terminal
 
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow, bail};
 use clap::Parser;
 use colored::Colorize;
 use config::set_basic_configs;
 use dialoguer::Select;
+use futures::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
 use log::{debug, info, warn};
-use reqwest::Client;
-use strsim::levenshtein;
-
-use crate::auth::get_credentials;
-use crate::cli::{CliArgs, Commands};
-use crate::error::log_error_chain;
-use crate::obs::{
-    // OBS operations
-    create_bucket,
-    delete_buckets,
-    delete_object,
-    download_object,
-    list_buckets,
-    list_objects,
-    list_regions,
-    upload_object,
-    upload_objects,
+
+use crate::cli::{ChunkArgs, CliArgs, Commands, CopyObjectArgs, ListObjectsArgs, PartArgs};
+use crate::output::{
+    ActionResult, Printer, readable_bucket_metadata, readable_buckets, readable_object_metadata,
+    readable_parts, readable_uploads,
+};
+use obsctl::auth::get_credentials;
+use obsctl::error::log_error_chain;
+use obsctl::region::{HUAWEI_CLOUD_REGIONS, fuzzy_match_region};
+use obsctl::sync::{OBS_URL_SCHEME, parse_obs_url};
+use obsctl::{
+    CopyOptions, Credentials, DownloadOptions, ListObjectsOptions, MetadataDirective, ObsClient,
+    RetryPolicy, SyncDirection, SyncItem, SyncOp, UploadOptions, WalkOptions,
 };
 
-// Maximum allowed edit distance for fuzzy region name matching
-// 4 should be low enough to be unnoticeable
-const LEVENSHTEIN_THRESHOLD: usize = 4;
-
-// Huawei Cloud main regions and their project names
-const HUAWEI_CLOUD_REGIONS: &[(&str, &str)] = &[
-    ("santiago", "la-south-2"),
-    ("johannesburg", "af-south-1"),
-    ("bangkok", "ap-southeast-2"),
-    ("hong-kong", "ap-southeast-1"),
-    ("singapore", "ap-southeast-3"),
-    ("beijing", "cn-north-4"),
-    ("guiyang", "cn-southwest-2"),
-    ("shanghai", "cn-east-3"),
-    ("mexico-city", "la-north-1"),
-    ("sao-paulo", "sa-brazil-1"),
-    ("riyadh", "me-west-1"),
-    ("istanbul", "tr-west-1"),
-];
+// Shortcut for starting a generic spinner
+macro_rules! init_spinner {
+    ($message:expr) => {{
+        let spinner = ProgressBar::new_spinner();
+        spinner.set_style(
+            ProgressStyle::default_spinner()
+                .template(&format!("{{spinner:.red}} {}", $message))
+                .unwrap(),
+        );
+        spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+        spinner
+    }};
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -63,48 +54,18 @@ async fn main() -> Result<()> {
     let command_result = match args.command {
         Commands::Setup => {
             debug!("Executing 'setup' command");
-            set_basic_configs()?;
-            Ok(())
+            set_basic_configs()
         }
         _ => {
-            let project_name = match args.region {
-                Some(r) => fuzzy_match_region(&r.to_lowercase()),
-                None => match std::env::var("HUAWEICLOUD_SDK_REGION") {
-                    Ok(r) => {
-                        info!("Using region from environment variable: {}", r.cyan());
-                        fuzzy_match_region(&r.to_lowercase())
-                    }
-                    Err(_) => {
-                        let items: Vec<String> = HUAWEI_CLOUD_REGIONS
-                            .iter()
-                            .map(|(name, code)| format!("{} ({})", name, code))
-                            .collect();
-
-                        let selection = Select::new()
-                            .with_prompt("Select a Huawei Cloud region")
-                            .items(&items)
-                            .default(0)
-                            .interact();
-
-                        let index = match selection {
-                            Ok(i) => i,
-                            Err(e) => {
-                                log_error_chain(e.into());
-                                exit(1);
-                            }
-                        };
-
-                        let chosen = HUAWEI_CLOUD_REGIONS[index].1.to_string();
-                        info!("Using region from menu: {}", chosen.cyan());
-                        unsafe {
-                            std::env::set_var("HUAWEICLOUD_SDK_REGION", &chosen);
-                        }
-                        chosen
-                    }
-                },
+            let project_name = match resolve_region(args.region.clone()) {
+                Ok(r) => r,
+                Err(e) => {
+                    log_error_chain(e);
+                    exit(1);
+                }
             };
 
-            let credentials = match get_credentials(args.ak, args.sk) {
+            let credentials = match get_credentials(args.ak.clone(), args.sk.clone()) {
                 Ok(creds) => creds,
                 Err(e) => {
                     log_error_chain(e);
@@ -112,135 +73,717 @@ async fn main() -> Result<()> {
                 }
             };
 
-            let client = Client::new();
-
-            match args.command {
-                Commands::Create(sub_args) => {
-                    debug!("Executing 'create' command");
-                    create_bucket(&client, &sub_args.bucket, project_name, &credentials).await
-                }
-                Commands::ListBuckets => {
-                    debug!("Executing 'list-buckets' command");
-                    list_buckets(&client, project_name, &credentials).await
+            let obs = match build_client(project_name, credentials, &args) {
+                Ok(obs) => obs,
+                Err(e) => {
+                    log_error_chain(e);
+                    exit(1);
                 }
-                Commands::ListObjects(sub_args) => {
-                    debug!("Executing 'list-objects' command");
-                    list_objects(
-                        &client,
+            };
+
+            run_command(&obs, args.command, &Printer::new(args.output)).await
+        }
+    };
+
+    if let Err(e) = command_result {
+        log_error_chain(e);
+        exit(1);
+    }
+
+    Ok(())
+}
+
+/// Dispatches a subcommand to the matching OBS operation and prints its result.
+async fn run_command(obs: &ObsClient, command: Commands, out: &Printer) -> Result<()> {
+    match command {
+        Commands::Create(sub_args) => {
+            debug!("Executing 'create' command");
+            obs.create_bucket(&sub_args.bucket).await?;
+            out.actions(vec![ActionResult::ok(
+                "create-bucket",
+                &sub_args.bucket,
+                None,
+                format!("Created bucket {}", sub_args.bucket.cyan()),
+            )])?;
+        }
+        Commands::ListBuckets => {
+            debug!("Executing 'list-buckets' command");
+            let spinner = init_spinner!("Listing all buckets");
+            let buckets = obs.list_buckets().await?;
+            spinner.finish_with_message("Done");
+            out.rows(buckets, readable_buckets)?;
+        }
+        Commands::ListObjects(sub_args) => {
+            debug!("Executing 'list-objects' command");
+            list_objects(obs, sub_args, out).await?;
+        }
+        Commands::DeleteBucket(sub_args) => {
+            debug!("Executing 'delete-bucket' command");
+            let spinner = init_spinner!(format!("Deleting {}", sub_args.buckets.join(", ")));
+            let results = obs.delete_buckets(sub_args.buckets).await;
+            spinner.finish_with_message("Done");
+            out.actions(
+                results
+                    .into_iter()
+                    .map(|(bucket_name, result)| match result {
+                        Ok(()) => ActionResult::ok(
+                            "delete-bucket",
+                            &bucket_name,
+                            None,
+                            format!("Deleted bucket {}", bucket_name.cyan()),
+                        ),
+                        Err(e) => ActionResult::failed("delete-bucket", &bucket_name, &e),
+                    })
+                    .collect(),
+            )?;
+        }
+        Commands::HeadBucket(sub_args) => {
+            debug!("Executing 'head-bucket' command");
+            let bucket = obs.head_bucket(&sub_args.bucket).await?;
+            out.details(&bucket, readable_bucket_metadata(&bucket))?;
+            // Lets scripts check for a bucket with the exit code
+            if !bucket.exists {
+                bail!("Bucket '{}' does not exist", sub_args.bucket);
+            }
+        }
+        Commands::UploadObject(sub_args) => {
+            debug!("Executing 'upload-object' command");
+            let upload_options = UploadOptions {
+                resume: sub_args.resume,
+                content_type: sub_args.content_type.clone(),
+                cache_control: sub_args.cache_control.clone(),
+                content_disposition: sub_args.content_disposition.clone(),
+                content_encoding: sub_args.content_encoding.clone(),
+                metadata: sub_args.meta.clone(),
+                ..upload_options(&sub_args.parts)?
+            };
+            if sub_args.file_paths == ["-"] {
+                let Some(key) = &sub_args.object_path else {
+                    bail!("Uploading stdin requires an object path (--object-path)");
+                };
+                let size = obs
+                    .upload_reader(&sub_args.bucket, key, tokio::io::stdin(), &upload_options)
+                    .await?;
+                out.actions(vec![ActionResult::ok(
+                    "upload",
+                    "-",
+                    Some(key.clone()),
+                    format!(
+                        "Uploaded {} from stdin to '{}'",
+                        bytesize::ByteSize(size),
+                        key.cyan()
+                    ),
+                )])?;
+            } else if let Some(dir) = &sub_args.recursive {
+                let options = WalkOptions {
+                    include: sub_args.include,
+                    exclude: sub_args.exclude,
+                    symlinks: sub_args.symlinks,
+                };
+                let results = obs
+                    .upload_directory(
                         &sub_args.bucket,
+                        Path::new(dir),
                         &sub_args.prefix,
-                        &sub_args.marker,
-                        project_name,
-                        &credentials,
+                        &options,
+                        &upload_options,
                     )
-                    .await
-                }
-                Commands::DeleteBucket(sub_args) => {
-                    debug!("Executing 'delete-bucket' command");
-                    delete_buckets(&client, sub_args.buckets, project_name, &credentials).await
+                    .await?;
+                batch_actions(out, upload_results(results), "upload", "files")?;
+            } else if sub_args.file_paths.len() == 1 {
+                let file_path = &sub_args.file_paths[0];
+                let key = obs
+                    .upload_object(
+                        &sub_args.bucket,
+                        file_path,
+                        sub_args.object_path.as_deref(),
+                        &upload_options,
+                    )
+                    .await?;
+                out.actions(vec![ActionResult::ok(
+                    "upload",
+                    file_path,
+                    Some(key.clone()),
+                    format!("Uploaded '{}'", key.cyan()),
+                )])?;
+            } else {
+                let results = obs
+                    .upload_objects(&sub_args.bucket, sub_args.file_paths, &upload_options)
+                    .await;
+                batch_actions(out, upload_results(results), "upload", "files")?;
+            }
+        }
+        Commands::DownloadObject(sub_args) => {
+            debug!("Executing 'download-object' command");
+            if sub_args.stdout {
+                let object_path = sub_args.object_path.unwrap_or_default();
+                return download_to_stdout(obs, &sub_args.bucket, &object_path).await;
+            }
+            let download_options = download_options(&sub_args.chunks);
+            let results = match (&sub_args.prefix, &sub_args.object_path) {
+                (Some(prefix), _) if sub_args.recursive => {
+                    obs.download_prefix(
+                        &sub_args.bucket,
+                        prefix,
+                        sub_args.output_dir.as_deref(),
+                        sub_args.concurrency as usize,
+                        &download_options,
+                    )
+                    .await?
                 }
-                Commands::UploadObject(sub_args) => {
-                    debug!("Executing 'upload-object' command");
-                    if sub_args.file_paths.len() == 1 {
-                        upload_object(
-                            &client,
+                (_, Some(object_path)) => {
+                    let local_path = obs
+                        .download_object(
                             &sub_args.bucket,
-                            project_name,
-                            &sub_args.file_paths[0],
-                            &sub_args.object_path,
-                            &credentials,
+                            object_path,
+                            sub_args.output_dir.as_deref(),
+                            &download_options,
                         )
-                        .await
-                    } else {
-                        upload_objects(
-                            &client,
-                            &sub_args.bucket,
-                            project_name,
-                            sub_args.file_paths,
-                            &credentials,
-                        )
-                        .await
-                    }
+                        .await?;
+                    vec![(object_path.clone(), Ok(local_path))]
                 }
-                Commands::DownloadObject(sub_args) => {
-                    debug!("Executing 'download-object' command");
-                    download_object(
-                        &client,
-                        &sub_args.bucket,
-                        project_name,
-                        &sub_args.object_path,
-                        &sub_args.output_dir,
-                        &credentials,
-                    )
-                    .await
+                _ => unreachable!(), // clap requires either --object-path or --recursive --prefix
+            };
+            batch_actions(out, download_results(results), "download", "objects")?;
+        }
+        Commands::Cat(sub_args) => {
+            debug!("Executing 'cat' command");
+            download_to_stdout(obs, &sub_args.bucket, &sub_args.object_path).await?;
+        }
+        Commands::HeadObject(sub_args) => {
+            debug!("Executing 'head-object' command");
+            let object = obs
+                .stat_object(&sub_args.bucket, &sub_args.object_path)
+                .await?;
+            out.details(&object, readable_object_metadata(&object))?;
+        }
+        Commands::DeleteObject(sub_args) => {
+            debug!("Executing 'delete-object' command");
+            let Some(object_path) = sub_args.object_path else {
+                let prefix = sub_args.prefix.unwrap_or_default();
+                let spinner = init_spinner!(format!("Deleting objects under '{prefix}'"));
+                let results = obs
+                    .delete_matching(&sub_args.bucket, &prefix, &sub_args.glob, sub_args.quiet)
+                    .await;
+                spinner.finish_and_clear();
+                let results = results?;
+
+                let total = results.len();
+                let results = delete_results(results, sub_args.quiet);
+                let failed = results.iter().filter(|r| !r.success).count();
+                out.actions(results)?;
+                info!(
+                    "Deleted {} of {total} objects",
+                    (total - failed).to_string().cyan()
+                );
+                // Lets scripts notice a partial delete
+                if failed > 0 {
+                    bail!("Failed to delete {failed} of {total} objects");
                 }
-                Commands::DeleteObject(sub_args) => {
-                    debug!("Executing 'delete-object' command");
-                    delete_object(
-                        &client,
-                        &sub_args.bucket,
-                        project_name,
-                        &sub_args.object_path,
-                        &credentials,
-                    )
-                    .await
+                return Ok(());
+            };
+
+            let spinner = init_spinner!(format!("Deleting object {object_path}"));
+            obs.delete_object(&sub_args.bucket, &object_path).await?;
+            spinner.finish_with_message("Done");
+            out.actions(vec![ActionResult::ok(
+                "delete-object",
+                &object_path,
+                None,
+                format!("Deleted '{}'", object_path.cyan()),
+            )])?;
+        }
+        Commands::CopyObject(sub_args) => {
+            debug!("Executing 'copy-object' command");
+            let (src_bucket, src_key, dst_bucket, dst_key) = copy_locations(&sub_args)?;
+            let options = copy_options(&sub_args)?;
+            let spinner = init_spinner!(format!("Copying {}", sub_args.source));
+            let result = obs
+                .copy_object(&src_bucket, &src_key, &dst_bucket, &dst_key, &options)
+                .await;
+            spinner.finish_and_clear();
+            result?;
+            let destination = format!("{OBS_URL_SCHEME}{dst_bucket}/{dst_key}");
+            out.actions(vec![ActionResult::ok(
+                "copy",
+                &sub_args.source,
+                Some(destination.clone()),
+                format!(
+                    "Copied '{}' to '{}'",
+                    sub_args.source.green(),
+                    destination.cyan()
+                ),
+            )])?;
+        }
+        Commands::MoveObject(sub_args) => {
+            debug!("Executing 'move-object' command");
+            let (src_bucket, src_key, dst_bucket, dst_key) = copy_locations(&sub_args)?;
+            let options = copy_options(&sub_args)?;
+            let spinner = init_spinner!(format!("Moving {}", sub_args.source));
+            let result = obs
+                .move_object(&src_bucket, &src_key, &dst_bucket, &dst_key, &options)
+                .await;
+            spinner.finish_and_clear();
+            result?;
+            let destination = format!("{OBS_URL_SCHEME}{dst_bucket}/{dst_key}");
+            out.actions(vec![ActionResult::ok(
+                "move",
+                &sub_args.source,
+                Some(destination.clone()),
+                format!(
+                    "Moved '{}' to '{}'",
+                    sub_args.source.green(),
+                    destination.cyan()
+                ),
+            )])?;
+        }
+        Commands::ListUploads(sub_args) => {
+            debug!("Executing 'list-uploads' command");
+            let spinner = init_spinner!("Listing multipart uploads");
+            let uploads = obs
+                .list_multipart_uploads(&sub_args.bucket, sub_args.prefix.as_deref())
+                .await;
+            spinner.finish_and_clear();
+            out.rows(uploads?, readable_uploads)?;
+        }
+        Commands::ListParts(sub_args) => {
+            debug!("Executing 'list-parts' command");
+            let parts = obs
+                .list_parts(&sub_args.bucket, &sub_args.object_path, &sub_args.upload_id)
+                .await?;
+            out.rows(parts, readable_parts)?;
+        }
+        Commands::AbortUpload(sub_args) => {
+            debug!("Executing 'abort-upload' command");
+            let results = match (
+                sub_args.older_than,
+                sub_args.object_path,
+                sub_args.upload_id,
+            ) {
+                (Some(older_than), _, _) => obs
+                    .abort_multipart_uploads(&sub_args.bucket, older_than)
+                    .await?
+                    .into_iter()
+                    .map(|(upload, result)| (upload.key, upload.upload_id, result))
+                    .collect(),
+                (None, Some(key), Some(upload_id)) => {
+                    let result = obs
+                        .abort_multipart_upload(&sub_args.bucket, &key, &upload_id)
+                        .await;
+                    vec![(key, upload_id, result)]
                 }
-                Commands::ListRegions => {
-                    debug!("Executing 'list-regions' command");
-                    list_regions(HUAWEI_CLOUD_REGIONS).await
+                _ => unreachable!(), // clap requires --older-than or both --object-path and --upload-id
+            };
+            out.actions(
+                results
+                    .into_iter()
+                    .map(|(key, upload_id, result)| match result {
+                        Ok(()) => ActionResult::ok(
+                            "abort-upload",
+                            &key,
+                            Some(upload_id.clone()),
+                            format!("Aborted upload {} of '{}'", upload_id, key.cyan()),
+                        ),
+                        Err(e) => ActionResult::failed("abort-upload", &key, &e),
+                    })
+                    .collect(),
+            )?;
+        }
+        Commands::Sync(sub_args) => {
+            debug!("Executing 'sync' command");
+            let (direction, (bucket, prefix), dir) = match (
+                parse_obs_url(&sub_args.source),
+                parse_obs_url(&sub_args.destination),
+            ) {
+                (None, Some(remote)) => (SyncDirection::Upload, remote, &sub_args.source),
+                (Some(remote), None) => (SyncDirection::Download, remote, &sub_args.destination),
+                _ => bail!(
+                    "Either the source or the destination must be a bucket location like {}, not both",
+                    "obs://bucket/prefix".yellow()
+                ),
+            };
+
+            let spinner = init_spinner!("Comparing files");
+            let plan = obs
+                .sync_plan(&bucket, &prefix, Path::new(dir), direction, sub_args.delete)
+                .await;
+            spinner.finish_and_clear();
+            let plan = plan?;
+
+            if plan.is_empty() && out.is_table() {
+                info!(
+                    "Nothing to sync, {} is up to date",
+                    sub_args.destination.cyan()
+                );
+            }
+            if sub_args.dry_run {
+                out.actions(sync_results(
+                    plan.into_iter().map(|item| (item, Ok(()))).collect(),
+                    true,
+                ))?;
+            } else {
+                let results = obs
+                    .apply_sync(
+                        &bucket,
+                        plan,
+                        sub_args.concurrency as usize,
+                        &upload_options(&sub_args.parts)?,
+                        &download_options(&sub_args.chunks),
+                    )
+                    .await;
+                let total = results.len();
+                let results = sync_results(results, false);
+                let failed = results.iter().filter(|r| !r.success).count();
+                out.actions(results)?;
+                if failed > 0 {
+                    bail!("Failed to sync {failed} of {total} files");
                 }
-                _ => unreachable!(), // Should not happen as all commands are handled
             }
         }
+        Commands::ListRegions => {
+            debug!("Executing 'list-regions' command");
+            out.regions(HUAWEI_CLOUD_REGIONS)?;
+        }
+        Commands::Setup => unreachable!(), // Handled before a client is built
+    }
+
+    Ok(())
+}
+
+// Results of batch uploads, keyed by local file path
+fn upload_results(results: Vec<(String, Result<String>)>) -> Vec<ActionResult> {
+    results
+        .into_iter()
+        .map(|(file_path, result)| match result {
+            Ok(key) => ActionResult::ok(
+                "upload",
+                &file_path,
+                Some(key.clone()),
+                format!(
+                    "Successfully uploaded '{}' to '{}'",
+                    file_path.green(),
+                    key.cyan()
+                ),
+            ),
+            Err(e) => ActionResult::failed("upload", &file_path, &e),
+        })
+        .collect()
+}
+
+// Results of batch deletes, only failures when `quiet`
+fn delete_results(results: Vec<(String, Result<()>)>, quiet: bool) -> Vec<ActionResult> {
+    results
+        .into_iter()
+        .filter(|(_, result)| !quiet || result.is_err())
+        .map(|(key, result)| match result {
+            Ok(()) => ActionResult::ok(
+                "delete-object",
+                &key,
+                None,
+                format!("Deleted '{}'", key.cyan()),
+            ),
+            Err(e) => ActionResult::failed("delete-object", &key, &e),
+        })
+        .collect()
+}
+
+// Prints the outcome of a batch, failing if any item failed so scripts notice partial batches
+fn batch_actions(out: &Printer, results: Vec<ActionResult>, verb: &str, items: &str) -> Result<()> {
+    let total = results.len();
+    let failed = results.iter().filter(|r| !r.success).count();
+    out.actions(results)?;
+    if failed > 0 {
+        bail!("Failed to {verb} {failed} of {total} {items}");
+    }
+    Ok(())
+}
+
+// Streams an object to stdout, the object itself is the only output
+async fn download_to_stdout(obs: &ObsClient, bucket: &str, object_path: &str) -> Result<()> {
+    let size = obs
+        .download_to_writer(bucket, object_path, &mut tokio::io::stdout())
+        .await?;
+    debug!("Wrote {size} bytes of '{object_path}' to stdout");
+    Ok(())
+}
+
+// Results of downloads, keyed by object path
+fn download_results(results: Vec<(String, Result<PathBuf>)>) -> Vec<ActionResult> {
+    results
+        .into_iter()
+        .map(|(key, result)| match result {
+            Ok(local_path) => ActionResult::ok(
+                "download",
+                &key,
+                Some(local_path.display().to_string()),
+                format!(
+                    "Successfully downloaded '{}' to '{}'",
+                    key.cyan(),
+                    local_path.display().to_string().green()
+                ),
+            ),
+            Err(e) => ActionResult::failed("download", &key, &e),
+        })
+        .collect()
+}
+
+// Results of a sync, or the plan itself with `dry_run`
+fn sync_results(results: Vec<(SyncItem, Result<()>)>, dry_run: bool) -> Vec<ActionResult> {
+    results
+        .into_iter()
+        .map(|(item, result)| match result {
+            Ok(()) => {
+                let verb = match (item.op, dry_run) {
+                    (SyncOp::Upload, false) => "Uploaded",
+                    (SyncOp::Download, false) => "Downloaded",
+                    (SyncOp::DeleteObject | SyncOp::DeleteFile, false) => "Deleted",
+                    (SyncOp::Upload, true) => "Would upload",
+                    (SyncOp::Download, true) => "Would download",
+                    (SyncOp::DeleteObject | SyncOp::DeleteFile, true) => "Would delete",
+                };
+                let target = match item.op {
+                    SyncOp::DeleteFile => item.local_path.display().to_string(),
+                    _ => item.key.clone(),
+                };
+                ActionResult::ok(
+                    item.op.as_str(),
+                    &item.key,
+                    Some(item.reason.to_string()),
+                    format!("{verb} '{}' ({})", target.cyan(), item.reason),
+                )
+            }
+            Err(e) => ActionResult::failed(item.op.as_str(), &item.key, &e),
+        })
+        .collect()
+}
+
+/// Lists objects as a single page, every page at once or streamed page by page.
+async fn list_objects(obs: &ObsClient, sub_args: ListObjectsArgs, out: &Printer) -> Result<()> {
+    let delimiter = match sub_args.delimiter {
+        Some(delimiter) => Some(delimiter),
+        None => sub_args.dir.then(|| "/".to_string()),
+    };
+
+    // Browsing "logs" in directory view means browsing "logs/"
+    let prefix = match (sub_args.prefix, &delimiter) {
+        (Some(prefix), Some(delimiter))
+            if sub_args.dir && !prefix.ends_with(delimiter.as_str()) =>
+        {
+            Some(format!("{prefix}{delimiter}"))
+        }
+        (prefix, _) => prefix,
     };
 
-    if let Err(e) = command_result {
-        log_error_chain(e);
-        exit(1);
+    let options = ListObjectsOptions {
+        prefix,
+        marker: sub_args.marker,
+        max_keys: sub_args.max_keys,
+        delimiter,
+    };
+
+    if sub_args.stream {
+        let out = &out.streaming();
+        let mut pages =
+            std::pin::pin!(obs.list_objects_pages(&sub_args.bucket, options, sub_args.limit));
+        let mut listed = 0;
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            let entries = page.objects.len() + page.common_prefixes.len();
+            // Empty pages only matter if nothing was printed at all
+            if entries > 0 || (listed == 0 && !page.is_truncated) {
+                listed += entries;
+                out.listing(page)?;
+            }
+        }
+        info!("Listed {} entries", listed.to_string().cyan());
+        return Ok(());
+    }
+
+    let spinner = init_spinner!(format!("Listing objects in {}", sub_args.bucket));
+    let listing = if sub_args.all || sub_args.limit.is_some() {
+        obs.list_objects(&sub_args.bucket, options, sub_args.limit)
+            .await?
+    } else {
+        obs.list_objects_page(&sub_args.bucket, &options).await?
+    };
+    spinner.finish_with_message("Done");
+
+    let next_marker = listing.next_marker.clone();
+    out.listing(listing)?;
+
+    if let Some(next_marker) = next_marker {
+        warn!(
+            "More objects available, use --all or --marker {}",
+            next_marker.yellow()
+        );
     }
 
     Ok(())
 }
 
-/// Returns the Huawei Cloud project name matching input exactly or approximately.
-pub fn fuzzy_match_region(input_region: &str) -> String {
-    debug!("Pattern matching region");
+// Source and destination buckets and keys of a copy or move
+fn copy_locations(args: &CopyObjectArgs) -> Result<(String, String, String, String)> {
+    let location = |url: &str| {
+        parse_obs_url(url).ok_or_else(|| {
+            anyhow!(
+                "'{url}' isn't an object location like {}",
+                "obs://bucket/key".yellow()
+            )
+        })
+    };
+    let (src_bucket, src_key) = location(&args.source)?;
+    let (dst_bucket, mut dst_key) = location(&args.destination)?;
 
-    // Try to find an exact match of the project names
-    if let Some((_, code)) = HUAWEI_CLOUD_REGIONS
-        .iter()
-        .find(|(_, code)| code == &input_region)
+    let Some(name) = src_key.rsplit('/').next().filter(|name| !name.is_empty()) else {
+        bail!("'{}' doesn't name an object", args.source);
+    };
+    // Like cp, a "folder" destination keeps the source's name
+    if dst_key.is_empty() || dst_key.ends_with('/') {
+        dst_key.push_str(name);
+    }
+    Ok((src_bucket, src_key, dst_bucket, dst_key))
+}
+
+// Metadata handling of a copy or move, overrides imply replacing the source's metadata
+fn copy_options(args: &CopyObjectArgs) -> Result<CopyOptions> {
+    let overrides = args.content_type.is_some() || !args.meta.is_empty();
+    let metadata_directive = match args.metadata_directive {
+        Some(MetadataDirective::Copy) if overrides => {
+            bail!("--content-type and --meta need --metadata-directive replace")
+        }
+        Some(directive) => directive,
+        None if overrides => MetadataDirective::Replace,
+        None => MetadataDirective::Copy,
+    };
+
+    Ok(CopyOptions {
+        metadata_directive,
+        content_type: args.content_type.clone(),
+        metadata: args.meta.clone(),
+    })
+}
+
+/// Multipart upload settings, CLI flags take precedence over environment variables.
+fn upload_options(args: &PartArgs) -> Result<UploadOptions> {
+    let mut options = UploadOptions {
+        multipart_threshold: args.multipart_threshold.as_u64(),
+        ..UploadOptions::default()
+    };
+
+    if let Some(part_size) = args.part_size {
+        options.part_size = part_size.as_u64();
+    } else if let Ok(part_size) = std::env::var("HUAWEICLOUD_SDK_PART_SIZE") {
+        options.part_size = part_size
+            .parse::<bytesize::ByteSize>()
+            .map_err(|e| anyhow!("Invalid HUAWEICLOUD_SDK_PART_SIZE '{part_size}': {e}"))?
+            .as_u64();
+    }
+
+    if let Some(concurrency) = args.part_concurrency {
+        options.part_concurrency = concurrency as usize;
+    } else if let Ok(concurrency) = std::env::var("HUAWEICLOUD_SDK_PART_CONCURRENCY") {
+        options.part_concurrency = concurrency
+            .parse()
+            .ok()
+            .filter(|&n| n > 0)
+            .with_context(|| {
+                format!("Invalid HUAWEICLOUD_SDK_PART_CONCURRENCY '{concurrency}', expected a positive number")
+            })?;
+    }
+
+    debug!("Upload options: {options:?}");
+    Ok(options)
+}
+
+/// Ranged download settings.
+fn download_options(args: &ChunkArgs) -> DownloadOptions {
+    DownloadOptions {
+        chunk_size: args.chunk_size.as_u64(),
+        concurrency: args.chunk_concurrency as usize,
+    }
+}
+
+/// Creates the OBS client, CLI flags take precedence over environment variables.
+fn build_client(region: String, credentials: Credentials, args: &CliArgs) -> Result<ObsClient> {
+    let mut builder = ObsClient::builder(region, credentials);
+
+    if let Some(endpoint) = args
+        .endpoint
+        .clone()
+        .or_else(|| std::env::var("HUAWEICLOUD_SDK_ENDPOINT").ok())
     {
-        let name = code.to_string();
-        info!("Exact matched project name {}", name.cyan());
-        name
-    } else {
-        // Attempt fuzzy matching using levenshtein distance
-        match HUAWEI_CLOUD_REGIONS
-            .iter()
-            .map(|(name, code)| (levenshtein(input_region, &name.to_lowercase()), code)) // Calculate distance between input and region names
-            .filter(|(dist, _)| *dist <= LEVENSHTEIN_THRESHOLD)                           // Filter matches within allowed threshold
-            .min_by_key(|(dist, _)| *dist)                                                // Pick the closest match
-            .map(|(_, code)| code.to_string())                                            // Extract the equivalent project name
-        {
-            // Fuzzy match found
-            Some(name) => {
-                warn!(
-                    "Fuzzy matched region {} into project name {}",
-                    input_region.yellow(),
-                    name.cyan()
-                );
-                name
+        info!("Using custom endpoint: {}", endpoint.cyan());
+        builder = builder.endpoint(endpoint);
+    }
+
+    let addressing_style = match args.addressing_style {
+        Some(style) => Some(style),
+        None => std::env::var("HUAWEICLOUD_SDK_ADDRESSING_STYLE")
+            .ok()
+            .map(|style| style.parse())
+            .transpose()?,
+    };
+    if let Some(style) = addressing_style {
+        debug!("Using {style} addressing style");
+        builder = builder.addressing_style(style);
+    }
+
+    let insecure_http = args.insecure_http
+        || std::env::var("HUAWEICLOUD_SDK_INSECURE_HTTP").is_ok_and(|v| v == "1" || v == "true");
+    builder = builder.insecure_http(insecure_http);
+
+    if let Some(ca_bundle) = args
+        .ca_bundle
+        .clone()
+        .or_else(|| std::env::var("HUAWEICLOUD_SDK_CA_BUNDLE").ok())
+    {
+        info!("Using CA bundle: {}", ca_bundle.cyan());
+        builder = builder.ca_bundle(ca_bundle);
+    }
+
+    if let (Some(cert), Some(key)) = (&args.client_cert, &args.client_key) {
+        builder = builder.client_identity(cert, key);
+    }
+
+    builder = builder.retry_policy(RetryPolicy {
+        max_attempts: args.max_attempts,
+        base_delay: Duration::from_millis(args.retry_backoff_ms),
+        jitter: !args.no_retry_jitter,
+        ..RetryPolicy::default()
+    });
+    builder = builder.memory_budget(args.max_memory.as_u64());
+
+    builder.build()
+}
+
+/// Picks the region from the CLI, the environment or an interactive menu, in that order.
+fn resolve_region(cli_region: Option<String>) -> Result<String> {
+    match cli_region {
+        Some(r) => fuzzy_match_region(&r.to_lowercase()),
+        None => match std::env::var("HUAWEICLOUD_SDK_REGION") {
+            Ok(r) => {
+                info!("Using region from environment variable: {}", r.cyan());
+                fuzzy_match_region(&r.to_lowercase())
             }
-            // Unsatisfactory matches
-            None => {
-                let err = anyhow::anyhow!(
-                    "Region '{}' not found or no close match within threshold",
-                    input_region.red()
-                );
-                log_error_chain(err);
-                std::process::exit(1);
+            Err(_) => {
+                let items: Vec<String> = HUAWEI_CLOUD_REGIONS
+                    .iter()
+                    .map(|(name, code)| format!("{} ({})", name, code))
+                    .collect();
+
+                let index = Select::new()
+                    .with_prompt("Select a Huawei Cloud region")
+                    .items(&items)
+                    .default(0)
+                    .interact()?;
+
+                let chosen = HUAWEI_CLOUD_REGIONS[index].1.to_string();
+                info!("Using region from menu: {}", chosen.cyan());
+                unsafe {
+                    std::env::set_var("HUAWEICLOUD_SDK_REGION", &chosen);
+                }
+                Ok(chosen)
             }
-        }
+        },
     }
 }
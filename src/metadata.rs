@@ -0,0 +1,171 @@
+use crate::endpoint;
+use crate::error::{ObsError, ObsErrorKind};
+use crate::obs::{Body, ObsClient, ObsRequest, ensure_success};
+use anyhow::{Result, anyhow};
+use reqwest::header::{
+    AsHeaderName, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, CONTENT_LENGTH,
+    CONTENT_TYPE, ETAG, EXPIRES, HeaderMap, LAST_MODIFIED,
+};
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Everything OBS reports about an object, from a HEAD request.
+#[derive(Clone, Debug, Serialize)]
+pub struct ObjectMetadata {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<String>,
+    pub etag: Option<String>,
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub content_encoding: Option<String>,
+    pub expires: Option<String>,
+    /// `STANDARD` unless OBS reports another class, e.g. `WARM` or `COLD`
+    pub storage_class: String,
+    /// Only set in buckets with versioning enabled
+    pub version_id: Option<String>,
+    /// Server-side encryption algorithm, e.g. `kms` or `AES256`
+    pub encryption: Option<String>,
+    /// KMS key of objects encrypted with `kms`
+    pub encryption_key_id: Option<String>,
+    /// Restore status of archived objects, e.g. `ongoing-request="false", expiry-date="..."`
+    pub restore: Option<String>,
+    /// When a lifecycle rule will delete the object
+    pub expiration: Option<String>,
+    /// User metadata, the `x-obs-meta-*` headers without their prefix
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// What OBS reports about a bucket, from a HEAD request.
+#[derive(Clone, Debug, Serialize)]
+pub struct BucketMetadata {
+    pub name: String,
+    pub exists: bool,
+    pub location: Option<String>,
+    /// `OBJECT`, or `POSIX` for parallel file systems
+    pub bucket_type: Option<String>,
+    pub storage_class: Option<String>,
+    /// `3az` for buckets stored across availability zones
+    pub az_redundancy: Option<String>,
+}
+
+impl ObsClient {
+    // Metadata of an object (size, ETag, user metadata...) as response headers of a HEAD
+    pub(crate) async fn head_object(&self, bucket_name: &str, key: &str) -> Result<HeaderMap> {
+        let url = self.endpoint().url(Some(bucket_name), key, "");
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), key, "");
+
+        let request = ObsRequest {
+            method: Method::HEAD,
+            url: &url,
+            body: Body::Text("".to_string()),
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        if !response.status().is_success() {
+            // HEAD responses have no body, so a 404 would only be "NotFound" otherwise
+            let err = ObsError::from_response(response)
+                .await
+                .not_found_as(ObsErrorKind::NoSuchKey);
+            return Err(anyhow::Error::new(err).context(format!("Head object '{key}' failed")));
+        }
+        Ok(response.headers().clone())
+    }
+
+    /// Reads the metadata of an object without downloading it.
+    pub async fn stat_object(&self, bucket_name: &str, key: &str) -> Result<ObjectMetadata> {
+        let headers = self.head_object(bucket_name, key).await?;
+        let size = header(&headers, CONTENT_LENGTH)
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| anyhow!("Missing object size in response"))?;
+
+        let metadata = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let name = name.as_str().strip_prefix("x-obs-meta-")?;
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                Some((name.to_string(), value))
+            })
+            .collect();
+
+        Ok(ObjectMetadata {
+            key: key.to_string(),
+            size,
+            last_modified: header(&headers, LAST_MODIFIED),
+            etag: header(&headers, ETAG),
+            content_type: header(&headers, CONTENT_TYPE),
+            cache_control: header(&headers, CACHE_CONTROL),
+            content_disposition: header(&headers, CONTENT_DISPOSITION),
+            content_encoding: header(&headers, CONTENT_ENCODING),
+            expires: header(&headers, EXPIRES),
+            storage_class: header(&headers, "x-obs-storage-class")
+                .unwrap_or_else(|| "STANDARD".to_string()),
+            version_id: header(&headers, "x-obs-version-id"),
+            encryption: header(&headers, "x-obs-server-side-encryption"),
+            encryption_key_id: header(&headers, "x-obs-server-side-encryption-kms-key-id"),
+            restore: header(&headers, "x-obs-restore"),
+            expiration: header(&headers, "x-obs-expiration"),
+            metadata,
+        })
+    }
+
+    /// Checks whether a bucket exists and reads its location and type.
+    ///
+    /// A missing bucket isn't an error, `exists` is false then. Buckets owned by someone else
+    /// fail with Access Denied.
+    pub async fn head_bucket(&self, bucket_name: &str) -> Result<BucketMetadata> {
+        let url = self.endpoint().url(Some(bucket_name), "", "");
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), "", "");
+
+        let request = ObsRequest {
+            method: Method::HEAD,
+            url: &url,
+            body: Body::Text("".to_string()),
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(BucketMetadata {
+                name: bucket_name.to_string(),
+                exists: false,
+                location: None,
+                bucket_type: None,
+                storage_class: None,
+                az_redundancy: None,
+            });
+        }
+        let response = ensure_success(response, "Head bucket").await?;
+        let headers = response.headers();
+
+        // Parallel file systems have the file interface enabled, other buckets are OBJECT ones
+        let bucket_type = header(headers, "x-obs-bucket-type").or_else(|| {
+            let posix = header(headers, "x-obs-fs-file-interface").as_deref() == Some("Enabled");
+            Some(if posix { "POSIX" } else { "OBJECT" }.to_string())
+        });
+
+        Ok(BucketMetadata {
+            name: bucket_name.to_string(),
+            exists: true,
+            location: header(headers, "x-obs-bucket-location"),
+            bucket_type,
+            storage_class: header(headers, "x-obs-storage-class"),
+            az_redundancy: header(headers, "x-obs-az-redundancy"),
+        })
+    }
+}
+
+fn header(headers: &HeaderMap, name: impl AsHeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+}
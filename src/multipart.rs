@@ -0,0 +1,825 @@
+use crate::buffer::PooledBuffer;
+use crate::endpoint;
+use crate::obs::{
+    Body, ContentType, FILE_CONCURRENCY, ObsClient, ObsRequest, UploadOptions, ensure_success,
+    transfer_bar, write_json_atomic,
+};
+use crate::xml::{CompleteMultipartUpload, MultipartUploadList, Part, PartList, xml_text};
+use crate::xml_to_struct_vec;
+use anyhow::{Context, Result, anyhow, bail};
+use base64::{Engine as _, engine::general_purpose};
+use bytes::Bytes;
+use bytesize::ByteSize;
+use chrono::{DateTime, Duration, Utc};
+use colored::Colorize;
+use futures::stream::{self, StreamExt};
+use log::{debug, info, warn};
+use quick_xml::se::to_string;
+use reqwest::Method;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncSeekExt};
+use tokio::task::JoinSet;
+
+/// Size of each part of a multipart upload by default, raised for files that need more parts.
+pub const DEFAULT_PART_SIZE: u64 = 50 * 1024 * 1024;
+/// Parts of a single upload sent at the same time by default.
+pub const DEFAULT_PART_CONCURRENCY: usize = 32;
+
+// OBS rejects uploads with more parts than this
+const MAX_PARTS: u64 = 10_000;
+// Limits OBS puts on every part but the last one
+const MIN_PART_SIZE: u64 = 100 * 1024;
+const MAX_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+// Auto-sized parts are rounded up to this
+const PART_SIZE_STEP: u64 = 1024 * 1024;
+
+/// Part size for a file of `file_size` bytes: `part_size`, raised if needed to fit in the
+/// 10,000 parts OBS allows. Fails if `part_size` is outside what OBS accepts (100 KiB to
+/// 5 GiB), or if the file is larger than the biggest object OBS can store.
+pub fn part_size_for(file_size: u64, part_size: u64) -> Result<u64> {
+    if !(MIN_PART_SIZE..=MAX_PART_SIZE).contains(&part_size) {
+        bail!(
+            "Part size must be between {} and {}, got {}",
+            ByteSize(MIN_PART_SIZE),
+            ByteSize(MAX_PART_SIZE),
+            ByteSize(part_size)
+        );
+    }
+    if file_size > MAX_PARTS * MAX_PART_SIZE {
+        bail!(
+            "{} is larger than the biggest object OBS can store ({})",
+            ByteSize(file_size),
+            ByteSize(MAX_PARTS * MAX_PART_SIZE)
+        );
+    }
+
+    let smallest = file_size.div_ceil(MAX_PARTS);
+    if part_size >= smallest {
+        return Ok(part_size);
+    }
+    Ok(smallest.next_multiple_of(PART_SIZE_STEP).min(MAX_PART_SIZE))
+}
+
+/// Progress of a multipart upload, saved as parts finish so it can be resumed.
+#[derive(Serialize, Deserialize)]
+struct UploadCheckpoint {
+    bucket: String,
+    key: String,
+    upload_id: String,
+    part_size: u64,
+    // Fingerprint of the file, only the same contents can be resumed
+    file_size: u64,
+    modified: SystemTime,
+    parts: Vec<Part>,
+}
+
+impl UploadCheckpoint {
+    // One checkpoint per bucket, key and file, kept in the user's cache directory
+    fn path(bucket_name: &str, key: &str, file_path: &str) -> Result<PathBuf> {
+        let file_path = fs::canonicalize(file_path)
+            .with_context(|| format!("Failed to resolve {file_path}"))?;
+        let id = md5::compute(format!("{bucket_name}\n{key}\n{}", file_path.display()));
+
+        let dir = dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("obsctl")
+            .join("uploads");
+        Ok(dir.join(format!("{id:x}.json")))
+    }
+
+    fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content)
+            .inspect_err(|e| warn!("Ignoring invalid checkpoint {}: {e}", path.display()))
+            .ok()
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        write_json_atomic(path, self)
+    }
+
+    fn is_same_file(&self, file_size: u64, modified: SystemTime) -> bool {
+        self.file_size == file_size && self.modified == modified
+    }
+
+    // Size part `part_number` must have, the last one can be smaller
+    fn part_len(&self, part_number: u32) -> u64 {
+        let offset = (part_number as u64 - 1) * self.part_size;
+        self.part_size.min(self.file_size.saturating_sub(offset))
+    }
+
+    fn part_count(&self) -> u64 {
+        self.file_size.div_ceil(self.part_size)
+    }
+}
+
+impl ObsClient {
+    /// Lists the multipart uploads started in a bucket that weren't completed or aborted yet.
+    pub async fn list_multipart_uploads(
+        &self,
+        bucket_name: &str,
+        prefix: Option<&str>,
+    ) -> Result<Vec<MultipartUploadList>> {
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), "", "?uploads");
+
+        let mut uploads = Vec::new();
+        let mut markers = None::<(String, String)>;
+        loop {
+            let mut query = "?uploads&encoding-type=url".to_string();
+            if let Some(prefix) = prefix {
+                query.push_str(&format!("&prefix={}", endpoint::encode_query_value(prefix)));
+            }
+            if let Some((key_marker, upload_id_marker)) = &markers {
+                query.push_str(&format!(
+                    "&key-marker={}&upload-id-marker={}",
+                    endpoint::encode_query_value(key_marker),
+                    endpoint::encode_query_value(upload_id_marker)
+                ));
+            }
+            let url = self.endpoint().url(Some(bucket_name), "", &query);
+
+            let request = ObsRequest {
+                method: Method::GET,
+                url: &url,
+                body: Body::Text("".to_string()),
+                content_type: None,
+                content_md5: "",
+                canonical_resource: &canonical_resource,
+                headers: HeaderMap::new(),
+            };
+
+            let response = self.generate_request(request).await?;
+            let raw_xml = ensure_success(response, "List multipart uploads")
+                .await?
+                .text()
+                .await
+                .context("Failed to read response body")?;
+
+            let mut page = xml_to_struct_vec!(
+                MultipartUploadList => "Upload" in &raw_xml, {
+                    Key => key,
+                    UploadId => upload_id,
+                    Initiated => initiated,
+                    StorageClass => storage_class,
+                }
+            )?;
+            let mut next_key_marker = xml_text(&raw_xml, "NextKeyMarker");
+            if xml_text(&raw_xml, "EncodingType").as_deref() == Some("url") {
+                for upload in &mut page {
+                    upload.key = endpoint::decode_url(&upload.key)?;
+                }
+                next_key_marker = next_key_marker
+                    .as_deref()
+                    .map(endpoint::decode_url)
+                    .transpose()?;
+            }
+            uploads.extend(page);
+
+            let next_markers = next_key_marker.zip(xml_text(&raw_xml, "NextUploadIdMarker"));
+            if xml_text(&raw_xml, "IsTruncated").as_deref() != Some("true")
+                || next_markers.is_none()
+            {
+                return Ok(uploads);
+            }
+            markers = next_markers;
+        }
+    }
+
+    /// Aborts a multipart upload, deleting the parts uploaded to it.
+    pub async fn abort_multipart_upload(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<()> {
+        let sub_resource = format!("?uploadId={upload_id}");
+        let url = self.endpoint().url(Some(bucket_name), key, &sub_resource);
+        let canonical_resource =
+            endpoint::canonical_resource(Some(bucket_name), key, &sub_resource);
+
+        let request = ObsRequest {
+            method: Method::DELETE,
+            url: &url,
+            body: Body::Text("".to_string()),
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        ensure_success(response, "Abort multipart upload").await?;
+        Ok(())
+    }
+
+    /// Aborts every multipart upload in a bucket initiated more than `older_than` ago.
+    ///
+    /// Returns the outcome of every abort, in listing order.
+    pub async fn abort_multipart_uploads(
+        &self,
+        bucket_name: &str,
+        older_than: Duration,
+    ) -> Result<Vec<(MultipartUploadList, Result<()>)>> {
+        if older_than < Duration::zero() {
+            bail!("Upload age can't be negative, it would match uploads still in progress");
+        }
+        let cutoff = Utc::now()
+            .checked_sub_signed(older_than)
+            .ok_or_else(|| anyhow!("Upload age of {} days is too large", older_than.num_days()))?;
+        let stale = self
+            .list_multipart_uploads(bucket_name, None)
+            .await?
+            .into_iter()
+            // Uploads with an unreadable date are left alone
+            .filter(|upload| {
+                DateTime::parse_from_rfc3339(&upload.initiated)
+                    .is_ok_and(|initiated| initiated < cutoff)
+            })
+            .collect::<Vec<_>>();
+        info!(
+            "Found {} uploads older than the cutoff",
+            stale.len().to_string().cyan()
+        );
+
+        Ok(stream::iter(stale)
+            .map(|upload| async move {
+                let result = self
+                    .abort_multipart_upload(bucket_name, &upload.key, &upload.upload_id)
+                    .await;
+                (upload, result)
+            })
+            .buffered(FILE_CONCURRENCY)
+            .collect()
+            .await)
+    }
+
+    /// Lists the parts uploaded so far to a multipart upload, following pagination.
+    pub async fn list_parts(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        upload_id: &str,
+    ) -> Result<Vec<PartList>> {
+        let sub_resource = format!("?uploadId={upload_id}");
+        let canonical_resource =
+            endpoint::canonical_resource(Some(bucket_name), key, &sub_resource);
+
+        let mut parts = Vec::new();
+        let mut marker = None::<String>;
+        loop {
+            let query = match &marker {
+                Some(marker) => format!("{sub_resource}&part-number-marker={marker}"),
+                None => sub_resource.clone(),
+            };
+            let url = self.endpoint().url(Some(bucket_name), key, &query);
+
+            let request = ObsRequest {
+                method: Method::GET,
+                url: &url,
+                body: Body::Text("".to_string()),
+                content_type: None,
+                content_md5: "",
+                canonical_resource: &canonical_resource,
+                headers: HeaderMap::new(),
+            };
+
+            let response = self.generate_request(request).await?;
+            let raw_xml = ensure_success(response, "List parts")
+                .await?
+                .text()
+                .await
+                .context("Failed to read response body")?;
+
+            parts.extend(xml_to_struct_vec!(
+                PartList => "Part" in &raw_xml, {
+                    PartNumber => part_number,
+                    LastModified => last_modified,
+                    ETag => etag,
+                    Size => size,
+                }
+            )?);
+
+            marker = xml_text(&raw_xml, "NextPartNumberMarker");
+            if xml_text(&raw_xml, "IsTruncated").as_deref() != Some("true") || marker.is_none() {
+                return Ok(parts);
+            }
+        }
+    }
+
+    /// Uploads everything read from `reader` (e.g. stdin) until it ends, returning its size.
+    ///
+    /// Small inputs are sent with a single PUT, larger ones in parts as they're read, holding
+    /// no more parts in memory than the client's budget allows. Unlike files, streams can't be
+    /// resumed, so a failed upload is always aborted.
+    pub async fn upload_reader<R: AsyncRead + Unpin>(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        mut reader: R,
+        options: &UploadOptions,
+    ) -> Result<u64> {
+        // The total size is unknown, so parts can't grow to fit it
+        let part_size = part_size_for(0, options.part_size)?;
+        let mut first = self.read_part(&mut reader, part_size).await?;
+        let size = first.len() as u64;
+        if first.is_empty() || (size < part_size && size < options.multipart_threshold) {
+            let content = first.freeze();
+            self.put_object(bucket_name, key, content.clone(), options)
+                .await?;
+            first.reclaim(content);
+            return Ok(size);
+        }
+
+        let (content_type, headers) = options.object_headers(key)?;
+        let upload_id = self
+            .initiate_multipart_upload(bucket_name, key, Some(content_type), headers)
+            .await?;
+        let result = self
+            .send_stream_parts(bucket_name, key, &upload_id, first, reader, options)
+            .await;
+        if result.is_err()
+            && let Err(e) = self
+                .abort_multipart_upload(bucket_name, key, &upload_id)
+                .await
+        {
+            warn!("Failed to abort upload {}: {e:#}", upload_id.yellow());
+        }
+        result
+    }
+
+    // Uploads parts as they're read from `reader`, starting with `first`, and completes the upload
+    async fn send_stream_parts<R: AsyncRead + Unpin>(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        upload_id: &str,
+        first: PooledBuffer,
+        reader: R,
+        options: &UploadOptions,
+    ) -> Result<u64> {
+        info!("Starting upload");
+        let bar = transfer_bar(0, "");
+
+        let buffers = stream::try_unfold((Some(first), reader), |(first, mut reader)| async move {
+            let buffer = match first {
+                Some(buffer) => buffer,
+                None => self.read_part(&mut reader, options.part_size).await?,
+            };
+            Ok::<_, anyhow::Error>((!buffer.is_empty()).then_some((buffer, (None, reader))))
+        });
+
+        let mut uploads = std::pin::pin!(
+            buffers
+                .enumerate()
+                .map(|(i, buffer)| {
+                    let part_number = i as u32 + 1;
+                    let bar = &bar;
+                    async move {
+                        let mut buffer = buffer?;
+                        if part_number as u64 > MAX_PARTS {
+                            bail!("Too many parts, exceeded {MAX_PARTS}, use a larger part size");
+                        }
+                        let size = buffer.len() as u64;
+                        // The total is unknown, it grows as parts are read
+                        bar.inc_length(size);
+                        let content = buffer.freeze();
+                        let etag = self
+                            .upload_part(bucket_name, key, upload_id, part_number, content.clone())
+                            .await?;
+                        buffer.reclaim(content);
+                        Ok((Part { part_number, etag }, size))
+                    }
+                })
+                .buffer_unordered(options.part_concurrency.max(1))
+        );
+
+        let mut parts = Vec::new();
+        let mut total_size = 0;
+        while let Some(result) = uploads.next().await {
+            let (part, size) = result?;
+            parts.push(part);
+            total_size += size;
+            bar.inc(size);
+        }
+
+        parts.sort_by_key(|p| p.part_number);
+        self.complete_multipart_upload(bucket_name, key, upload_id, parts)
+            .await?;
+        bar.finish_with_message("Done");
+        Ok(total_size)
+    }
+
+    /// Uploads a file in parts, checkpointing finished parts so the upload can be resumed.
+    pub(crate) async fn upload_multipart(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        file_path: &str,
+        file_size: u64,
+        options: &UploadOptions,
+    ) -> Result<()> {
+        let part_size = part_size_for(file_size, options.part_size)?;
+        if part_size != options.part_size {
+            info!(
+                "Using {} parts to stay within {MAX_PARTS} parts",
+                ByteSize(part_size)
+            );
+        }
+
+        let modified = fs::metadata(file_path)
+            .and_then(|metadata| metadata.modified())
+            .context("Failed to read file metadata")?;
+        let checkpoint_path = UploadCheckpoint::path(bucket_name, key, file_path)?;
+
+        let saved = if options.resume {
+            self.resumable_checkpoint(&checkpoint_path, file_size, modified)
+                .await
+        } else {
+            None
+        };
+        let mut checkpoint = match saved {
+            Some(checkpoint) => checkpoint,
+            None => {
+                // Its checkpoint is about to be replaced, the upload would be left billed
+                if let Some(stale) = UploadCheckpoint::load(&checkpoint_path) {
+                    if !options.resume {
+                        warn!(
+                            "Aborting upload {} left by an earlier run, use --resume to continue it instead",
+                            stale.upload_id.yellow()
+                        );
+                    }
+                    self.discard_upload(&stale, &checkpoint_path).await;
+                }
+
+                // A resumed upload keeps the headers it was started with
+                let (content_type, headers) = options.object_headers(key)?;
+                UploadCheckpoint {
+                    bucket: bucket_name.to_string(),
+                    key: key.to_string(),
+                    upload_id: self
+                        .initiate_multipart_upload(bucket_name, key, Some(content_type), headers)
+                        .await?,
+                    part_size,
+                    file_size,
+                    modified,
+                    parts: Vec::new(),
+                }
+            }
+        };
+        checkpoint.save(&checkpoint_path)?;
+        debug!(
+            "Checkpoint of upload saved to {}",
+            checkpoint_path.display()
+        );
+
+        if let Err(e) = self
+            .send_parts(file_path, &mut checkpoint, &checkpoint_path, options)
+            .await
+        {
+            if options.resume {
+                warn!(
+                    "Upload of {} failed, its checkpoint is kept so it can be resumed",
+                    key.yellow()
+                );
+            } else {
+                self.discard_upload(&checkpoint, &checkpoint_path).await;
+            }
+            return Err(e);
+        }
+
+        remove_checkpoint(&checkpoint_path);
+        Ok(())
+    }
+
+    // Uploads the parts missing from the checkpoint and completes the upload
+    async fn send_parts(
+        &self,
+        file_path: &str,
+        checkpoint: &mut UploadCheckpoint,
+        checkpoint_path: &Path,
+        options: &UploadOptions,
+    ) -> Result<()> {
+        info!("Starting upload");
+        let bar = transfer_bar(checkpoint.file_size, "");
+
+        let done = checkpoint
+            .parts
+            .iter()
+            .map(|part| part.part_number)
+            .collect::<HashSet<_>>();
+        bar.inc(done.iter().map(|&n| checkpoint.part_len(n)).sum());
+
+        let missing = (1..=checkpoint.part_count() as u32)
+            .filter(|n| !done.contains(n))
+            .map(|n| {
+                let offset = (n as u64 - 1) * checkpoint.part_size;
+                (n, offset, checkpoint.part_len(n))
+            })
+            .collect::<Vec<_>>();
+
+        // The checkpoint is updated while parts upload, so they get their own copies
+        let bucket_name = checkpoint.bucket.clone();
+        let key = checkpoint.key.clone();
+        let upload_id = checkpoint.upload_id.clone();
+
+        let spawn_part = |uploads: &mut JoinSet<_>,
+                          (part_number, offset, size): (u32, u64, u64)| {
+            let obs = self.clone();
+            let bucket_name = bucket_name.clone();
+            let key = key.clone();
+            let upload_id = upload_id.clone();
+            let file_path = file_path.to_string();
+
+            uploads.spawn(async move {
+                // Waits for room in the memory budget before reading anything
+                let mut buffer = obs.buffers.get(size as usize).await;
+                let mut file = tokio::fs::File::open(&file_path).await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+                buffer.fill_from(&mut file).await?;
+                if buffer.len() as u64 != size {
+                    return Err(anyhow!("{file_path} was truncated during the upload"));
+                }
+
+                let content = buffer.freeze();
+                let etag = obs
+                    .upload_part(&bucket_name, &key, &upload_id, part_number, content.clone())
+                    .await?;
+                buffer.reclaim(content);
+                Ok::<_, anyhow::Error>((Part { part_number, etag }, size))
+            });
+        };
+
+        // Tasks in a JoinSet are aborted when it's dropped, so none outlive a failed upload
+        let mut uploads = JoinSet::new();
+        let mut missing = missing.into_iter();
+        loop {
+            while uploads.len() < options.part_concurrency.max(1)
+                && let Some(part) = missing.next()
+            {
+                spawn_part(&mut uploads, part);
+            }
+            let Some(joined) = uploads.join_next().await else {
+                break;
+            };
+
+            let saved = joined
+                .map_err(anyhow::Error::from)
+                .and_then(|result| result)
+                .and_then(|(part, size)| {
+                    checkpoint.parts.push(part);
+                    checkpoint.save(checkpoint_path)?;
+                    bar.inc(size);
+                    Ok(())
+                });
+            if let Err(e) = saved {
+                // Parts still uploading could be stored after the upload is aborted
+                uploads.abort_all();
+                while uploads.join_next().await.is_some() {}
+                return Err(e);
+            }
+        }
+
+        checkpoint.parts.sort_by_key(|p| p.part_number);
+        self.complete_multipart_upload(
+            &checkpoint.bucket,
+            &checkpoint.key,
+            &checkpoint.upload_id,
+            checkpoint.parts.clone(),
+        )
+        .await?;
+
+        bar.finish_with_message("Done");
+        Ok(())
+    }
+
+    // Aborts a failed upload so its parts aren't billed, along with its checkpoint
+    async fn discard_upload(&self, checkpoint: &UploadCheckpoint, checkpoint_path: &Path) {
+        match self
+            .abort_multipart_upload(&checkpoint.bucket, &checkpoint.key, &checkpoint.upload_id)
+            .await
+        {
+            Ok(()) => debug!("Aborted failed upload {}", checkpoint.upload_id),
+            Err(e) => warn!(
+                "Failed to abort upload {} of {}, its parts are still stored: {e:#}",
+                checkpoint.upload_id.yellow(),
+                checkpoint.key
+            ),
+        }
+        remove_checkpoint(checkpoint_path);
+    }
+
+    // Checkpoint of an interrupted upload of the same file, with the parts OBS actually has
+    async fn resumable_checkpoint(
+        &self,
+        path: &Path,
+        file_size: u64,
+        modified: SystemTime,
+    ) -> Option<UploadCheckpoint> {
+        let Some(mut checkpoint) = UploadCheckpoint::load(path) else {
+            info!("No interrupted upload to resume, starting a new one");
+            return None;
+        };
+        if !checkpoint.is_same_file(file_size, modified) {
+            warn!("File changed since the upload was interrupted, starting a new one");
+            return None;
+        }
+
+        // Parts that finished after the last save are only known to OBS
+        let uploaded = match self
+            .list_parts(&checkpoint.bucket, &checkpoint.key, &checkpoint.upload_id)
+            .await
+        {
+            Ok(uploaded) => uploaded,
+            Err(e) => {
+                warn!("Can't resume upload {}: {e:#}", checkpoint.upload_id);
+                return None;
+            }
+        };
+        checkpoint.parts = uploaded
+            .into_iter()
+            .filter_map(|part| {
+                let part_number = part.part_number.parse().ok()?;
+                let size = part.size.parse::<u64>().ok()?;
+                // Parts of the wrong size are uploaded again, they are replaced on OBS
+                (size == checkpoint.part_len(part_number)).then_some(Part {
+                    part_number,
+                    etag: part.etag,
+                })
+            })
+            .collect();
+
+        info!(
+            "Resuming upload, {} of {} parts already uploaded",
+            checkpoint.parts.len(),
+            checkpoint.part_count()
+        );
+        Some(checkpoint)
+    }
+
+    // Starts a multipart upload and returns its id, `headers` set the object's metadata
+    pub(crate) async fn initiate_multipart_upload(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        content_type: Option<ContentType>,
+        headers: HeaderMap,
+    ) -> Result<String> {
+        let init_url = self.endpoint().url(Some(bucket_name), key, "?uploads");
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), key, "?uploads");
+
+        let init_request = ObsRequest {
+            method: Method::POST,
+            url: &init_url,
+            body: Body::Text("".to_string()),
+            content_type,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers,
+        };
+
+        let init_response = self.generate_request(init_request).await?;
+        let init_body = ensure_success(init_response, "Initiate")
+            .await?
+            .text()
+            .await?;
+
+        xml_text(&init_body, "UploadId").ok_or_else(|| anyhow!("Failed to parse UploadId"))
+    }
+
+    // Reads the next part of a stream, once the memory budget has room for it
+    async fn read_part<R: AsyncRead + Unpin>(
+        &self,
+        reader: &mut R,
+        part_size: u64,
+    ) -> Result<PooledBuffer> {
+        let mut buffer = self.buffers.get(part_size as usize).await;
+        buffer
+            .fill_from(reader)
+            .await
+            .context("Failed to read upload data")?;
+        Ok(buffer)
+    }
+
+    // Uploads a single part and returns its ETag
+    async fn upload_part(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        buffer: Bytes,
+    ) -> Result<String> {
+        let digest = md5::compute(&buffer);
+        let content_md5 = general_purpose::STANDARD.encode(digest.as_ref());
+
+        let sub_resource = format!("?partNumber={part_number}&uploadId={upload_id}");
+        let part_url = self.endpoint().url(Some(bucket_name), key, &sub_resource);
+        let canonical_resource =
+            endpoint::canonical_resource(Some(bucket_name), key, &sub_resource);
+
+        let part_request = ObsRequest {
+            method: Method::PUT,
+            url: &part_url,
+            body: Body::Binary(buffer),
+            content_type: Some(ContentType::ApplicationOctetStream),
+            content_md5: &content_md5,
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(part_request).await?;
+        let response = ensure_success(response, &format!("Part {part_number} upload")).await?;
+
+        Ok(response
+            .headers()
+            .get("Etag")
+            .ok_or_else(|| anyhow!("Missing ETag for part {}", part_number))?
+            .to_str()?
+            .to_string())
+    }
+
+    // Joins the uploaded parts into the final object
+    pub(crate) async fn complete_multipart_upload(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<Part>,
+    ) -> Result<()> {
+        let complete_xml = to_string(&CompleteMultipartUpload { parts })?;
+        let sub_resource = format!("?uploadId={upload_id}");
+        let complete_url = self.endpoint().url(Some(bucket_name), key, &sub_resource);
+        let canonical_resource =
+            endpoint::canonical_resource(Some(bucket_name), key, &sub_resource);
+
+        let complete_request = ObsRequest {
+            method: Method::POST,
+            url: &complete_url,
+            body: Body::Binary(complete_xml.into()),
+            content_type: Some(ContentType::ApplicationXml),
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let complete_response = self.generate_request(complete_request).await?;
+        ensure_success(complete_response, "Complete").await?;
+        Ok(())
+    }
+}
+
+fn remove_checkpoint(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        warn!("Failed to remove checkpoint {}: {e}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    #[test]
+    fn part_size_for_checks_bounds() {
+        assert_eq!(part_size_for(1, MIN_PART_SIZE).unwrap(), MIN_PART_SIZE);
+        assert_eq!(part_size_for(1, MAX_PART_SIZE).unwrap(), MAX_PART_SIZE);
+        assert!(part_size_for(1, MIN_PART_SIZE - 1).is_err());
+        assert!(part_size_for(1, MAX_PART_SIZE + 1).is_err());
+    }
+
+    #[test]
+    fn part_size_for_grows_to_fit_max_parts() {
+        assert_eq!(
+            part_size_for(MAX_PARTS * 8 * MIB, 8 * MIB).unwrap(),
+            8 * MIB
+        );
+        // One byte more needs a bigger part, rounded up to the next MiB
+        assert_eq!(
+            part_size_for(MAX_PARTS * 8 * MIB + 1, 8 * MIB).unwrap(),
+            9 * MIB
+        );
+        // A bigger part size than needed is kept
+        assert_eq!(
+            part_size_for(MAX_PARTS * 8 * MIB + 1, 64 * MIB).unwrap(),
+            64 * MIB
+        );
+    }
+
+    #[test]
+    fn part_size_for_rejects_objects_over_max_size() {
+        // 10,000 parts of 5 GiB, about 48.8 TiB
+        let max_size = MAX_PARTS * MAX_PART_SIZE;
+        assert_eq!(
+            part_size_for(max_size, DEFAULT_PART_SIZE).unwrap(),
+            MAX_PART_SIZE
+        );
+        assert!(part_size_for(max_size + 1, DEFAULT_PART_SIZE).is_err());
+    }
+}
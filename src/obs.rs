@@ -1,36 +1,36 @@
-use crate::HUAWEI_CLOUD_REGIONS;
-use crate::error::log_api_response;
-use crate::info;
+use crate::buffer::{BufferPool, DEFAULT_MEMORY_BUDGET};
+use crate::download::DownloadOptions;
+use crate::endpoint::{self, AddressingStyle, Endpoint};
+use crate::error::ObsError;
+use crate::multipart::{DEFAULT_PART_CONCURRENCY, DEFAULT_PART_SIZE};
+use crate::retry::{RetryPolicy, is_retryable_status};
+use crate::walk::{WalkOptions, local_path_for_key, walk_directory};
 use crate::xml::BucketList;
-use crate::xml::CompleteMultipartUpload;
+use crate::xml::CommonPrefix;
 use crate::xml::ObjectList;
-use crate::xml::Part;
+use crate::xml::xml_text;
 use crate::xml_to_struct_vec;
 use anyhow::{Context, Result, anyhow};
 use base64::{Engine as _, engine::general_purpose};
-use bytesize::ByteSize;
+use bytes::Bytes;
 use chrono::Utc;
 use colored::Colorize;
 use futures::future::join_all;
-use futures::stream::{FuturesUnordered, StreamExt};
+use futures::stream::{self, Stream, StreamExt};
 use hmac::{Hmac, Mac};
-use indicatif::{ProgressBar, ProgressStyle};
-use log::debug;
-use log::error;
-
-use quick_xml::se::to_string;
-use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{Client, Method, Response};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+
+use reqwest::header::{
+    CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_ENCODING, HeaderMap, HeaderName, HeaderValue,
+};
+use reqwest::{Certificate, Client, Identity, Method, Response};
+use serde::Serialize;
 use sha1::Sha1;
 use std::fs;
-use std::io::Read;
-use std::io::Seek;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use tabled::{Table, settings::style::Style};
-use tokio::sync::Semaphore;
-
 // REVIEW replace reqwest with ureq, the asynchronous functions can be deal with differently
 
 // HMAC-SHA1 type alias for OBS signing, not ideal 🤷
@@ -42,34 +42,179 @@ pub struct Credentials {
     pub sk: String,
 }
 
+/// Client for the OBS API, every operation is a method on it.
+///
+/// Cloning is cheap, the underlying HTTP client is reference counted. Clones share the
+/// memory budget of their uploads.
+#[derive(Clone)]
+pub struct ObsClient {
+    client: Client,
+    endpoint: Endpoint,
+    region: String,
+    credentials: Credentials,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) buffers: Arc<BufferPool>,
+}
+
+/// Configures an [`ObsClient`] before creating it.
+pub struct ObsClientBuilder {
+    region: String,
+    credentials: Credentials,
+    endpoint: Option<String>,
+    addressing_style: AddressingStyle,
+    insecure_http: bool,
+    ca_bundle: Option<PathBuf>,
+    client_identity: Option<(PathBuf, PathBuf)>,
+    retry_policy: RetryPolicy,
+    memory_budget: u64,
+}
+
+/// Filters and paging for object listings.
+#[derive(Clone, Debug, Default)]
+pub struct ListObjectsOptions {
+    /// Include only keys starting with this
+    pub prefix: Option<String>,
+    /// List keys after this one
+    pub marker: Option<String>,
+    /// Page size, OBS caps it at 1000
+    pub max_keys: Option<u32>,
+    /// Groups keys up to the next occurrence of this (usually `/`) into common prefixes
+    pub delimiter: Option<String>,
+}
+
+/// Settings for uploading files.
+#[derive(Clone, Debug)]
+pub struct UploadOptions {
+    /// Continue an interrupted multipart upload of the same file from its checkpoint
+    pub resume: bool,
+    /// Files this size or larger are uploaded in parts, smaller ones with a single PUT
+    pub multipart_threshold: u64,
+    /// Size of each part, raised for files that wouldn't fit in 10,000 parts
+    pub part_size: u64,
+    /// Parts of a single upload sent at the same time
+    pub part_concurrency: usize,
+    /// Content type of the objects, guessed from the extension of their keys when not set
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+    pub content_disposition: Option<String>,
+    pub content_encoding: Option<String>,
+    /// User metadata of the objects, sent as `x-obs-meta-<name>`
+    pub metadata: Vec<(String, String)>,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            resume: false,
+            multipart_threshold: DEFAULT_MULTIPART_THRESHOLD,
+            part_size: DEFAULT_PART_SIZE,
+            part_concurrency: DEFAULT_PART_CONCURRENCY,
+            content_type: None,
+            cache_control: None,
+            content_disposition: None,
+            content_encoding: None,
+            metadata: Vec::new(),
+        }
+    }
+}
+
+impl UploadOptions {
+    // Content type and metadata headers of an uploaded object
+    pub(crate) fn object_headers(&self, key: &str) -> Result<(ContentType, HeaderMap)> {
+        let content_type = match &self.content_type {
+            Some(mime) => ContentType::Other(mime.clone()),
+            None => mime_guess::from_path(key)
+                .first()
+                .map_or(ContentType::ApplicationOctetStream, |mime| {
+                    ContentType::Other(mime.to_string())
+                }),
+        };
+
+        let mut headers = metadata_headers(&self.metadata)?;
+        let standard = [
+            (CACHE_CONTROL, &self.cache_control),
+            (CONTENT_DISPOSITION, &self.content_disposition),
+            (CONTENT_ENCODING, &self.content_encoding),
+        ];
+        for (name, value) in standard {
+            if let Some(value) = value {
+                let value = HeaderValue::from_str(value)
+                    .with_context(|| format!("Invalid {name} '{value}'"))?;
+                headers.insert(name, value);
+            }
+        }
+        Ok((content_type, headers))
+    }
+}
+
+// User metadata as `x-obs-meta-*` headers
+pub(crate) fn metadata_headers(metadata: &[(String, String)]) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in metadata {
+        let header = HeaderName::from_bytes(format!("x-obs-meta-{name}").as_bytes())
+            .with_context(|| format!("Invalid metadata name '{name}'"))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value of metadata '{name}'"))?;
+        headers.insert(header, value);
+    }
+    Ok(headers)
+}
+
+/// A single page of an object listing.
+#[derive(Serialize)]
+pub struct ObjectListPage {
+    pub objects: Vec<ObjectList>,
+    /// Pseudo-folders, only returned when a delimiter is set
+    pub common_prefixes: Vec<CommonPrefix>,
+    pub is_truncated: bool,
+    /// Marker of the next page, if there is one
+    pub next_marker: Option<String>,
+}
+
+// Maximum page size accepted by OBS
+const MAX_KEYS_PER_PAGE: u32 = 1000;
+
+/// Size from which files are uploaded in parts by default.
+pub const DEFAULT_MULTIPART_THRESHOLD: u64 = 50 * 1024 * 1024;
+
+// Largest object a single PUT can upload, or a single copy can copy
+pub(crate) const MAX_PUT_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+// Files transferred at the same time by batch uploads
+pub(crate) const FILE_CONCURRENCY: usize = 8;
+
 /// Represents a structured request to the OBS API.
-struct ObsRequest<'a> {
-    method: Method,
-    url: &'a str,
-    credentials: &'a Credentials, // AK/SK
-    body: Body,
-    content_type: Option<ContentType>,
-    content_md5: &'a str,
-    canonical_resource: &'a str,
+pub(crate) struct ObsRequest<'a> {
+    pub(crate) method: Method,
+    pub(crate) url: &'a str,
+    pub(crate) body: Body,
+    pub(crate) content_type: Option<ContentType>,
+    pub(crate) content_md5: &'a str,
+    pub(crate) canonical_resource: &'a str,
+    // Sent as is, e.g. Range or If-Match
+    pub(crate) headers: HeaderMap,
 }
 
 // Workaround sending binary file data OR text to the API
-enum Body {
+pub(crate) enum Body {
     Text(String),
-    Binary(Vec<u8>),
+    Binary(Bytes),
 }
 
 #[derive(Clone)]
-enum ContentType {
+pub(crate) enum ContentType {
     ApplicationXml,
     ApplicationOctetStream,
+    // Any other MIME type, e.g. one kept from a copied object
+    Other(String),
 }
 
 impl ContentType {
-    fn as_str(&self) -> &'static str {
+    fn as_str(&self) -> &str {
         match self {
             ContentType::ApplicationXml => "application/xml",
             ContentType::ApplicationOctetStream => "application/octet-stream",
+            ContentType::Other(mime) => mime,
         }
     }
 }
@@ -80,7 +225,7 @@ macro_rules! query_params {
         let mut params = Vec::new();
         $(
             if let Some(v) = $val.as_deref() {
-                params.push(format!("{}={}", $key, v));
+                params.push(format!("{}={}", $key, endpoint::encode_query_value(v)));
             }
         )*
         if params.is_empty() {
@@ -91,606 +236,848 @@ macro_rules! query_params {
     }};
 }
 
-// Shortcut for starting a generic spinner
-macro_rules! init_spinner {
-    ($message:expr) => {{
-        let spinner = ProgressBar::new_spinner();
-        spinner.set_style(
-            ProgressStyle::default_spinner()
-                .template(&format!("{{spinner:.red}} {}", $message))
-                .unwrap(),
+impl ObsClient {
+    /// Creates a client for the public OBS endpoint of a region.
+    pub fn new(region: impl Into<String>, credentials: Credentials) -> Self {
+        let region = region.into();
+        Self {
+            client: Client::new(),
+            endpoint: Endpoint::for_region(&region),
+            region,
+            credentials,
+            retry_policy: RetryPolicy::default(),
+            buffers: BufferPool::new(DEFAULT_MEMORY_BUDGET),
+        }
+    }
+
+    /// Starts configuring a client, for custom endpoints or addressing styles.
+    pub fn builder(region: impl Into<String>, credentials: Credentials) -> ObsClientBuilder {
+        ObsClientBuilder {
+            region: region.into(),
+            credentials,
+            endpoint: None,
+            addressing_style: AddressingStyle::default(),
+            insecure_http: false,
+            ca_bundle: None,
+            client_identity: None,
+            retry_policy: RetryPolicy::default(),
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+        }
+    }
+
+    /// Region (project name) this client sends requests to.
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// Endpoint every request URL is built from.
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Sends a request to create an OBS bucket.
+    pub async fn create_bucket(&self, bucket_name: &str) -> Result<()> {
+        let url = self.endpoint.url(Some(bucket_name), "", "");
+        let body = Body::Text(format!(
+            "<CreateBucketConfiguration><Location>{}</Location></CreateBucketConfiguration>",
+            self.region
+        ));
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), "", "");
+
+        let request = ObsRequest {
+            method: Method::PUT,
+            url: &url,
+            body,
+            content_type: Some(ContentType::ApplicationXml),
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        ensure_success(response, "Create bucket").await?;
+        Ok(())
+    }
+
+    /// Sends a request to list all OBS buckets.
+    pub async fn list_buckets(&self) -> Result<Vec<BucketList>> {
+        let url = self.endpoint.url(None, "", "");
+        let body = Body::Text("".to_string());
+        let canonical_resource = endpoint::canonical_resource(None, "", "");
+
+        let request = ObsRequest {
+            method: Method::GET,
+            url: &url,
+            body,
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        let raw_xml = ensure_success(response, "List buckets")
+            .await?
+            .text()
+            .await
+            .context("Failed to read response body")?;
+
+        Ok(xml_to_struct_vec!(
+            BucketList => "Bucket" in &raw_xml, {
+                Name => name,
+                CreationDate => creation_date,
+                Location => location,
+                BucketType => bucket_type
+            }
+        )?)
+    }
+
+    /// Sends a request for a single page of objects in a bucket.
+    pub async fn list_objects_page(
+        &self,
+        bucket_name: &str,
+        options: &ListObjectsOptions,
+    ) -> Result<ObjectListPage> {
+        let query = query_params!(
+            "prefix" => options.prefix,
+            "marker" => options.marker,
+            "max-keys" => options.max_keys.map(|n| n.to_string()),
+            "delimiter" => options.delimiter,
+            // Keys are XML text otherwise, which can't hold every character a key can
+            "encoding-type" => Some("url"),
         );
-        spinner.enable_steady_tick(std::time::Duration::from_millis(80));
-        spinner
-    }};
-}
+        let url = self.endpoint.url(Some(bucket_name), "", &query);
+        let body = Body::Text("".to_string());
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), "", "");
+
+        let request = ObsRequest {
+            method: Method::GET,
+            url: &url,
+            body,
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        let raw_xml = ensure_success(response, "List objects")
+            .await?
+            .text()
+            .await
+            .context("Failed to read response body")?;
+
+        let mut objects = xml_to_struct_vec!(
+            ObjectList => "Contents" in &raw_xml, {
+                Key => key,
+                LastModified => last_modified,
+                ETag => etag,
+                Size => size,
+                StorageClass => storage_class,
+            }
+        )?;
 
-/// Sends a request to create an OBS bucket.
-pub async fn create_bucket(
-    client: &Client,
-    bucket_name: &str,
-    region: String,
-    credentials: &Credentials,
-) -> Result<()> {
-    let url = format!("http://{bucket_name}.obs.{region}.myhuaweicloud.com");
-    let body = Body::Text(format!(
-        "<CreateBucketConfiguration><Location>{region}</Location></CreateBucketConfiguration>"
-    ));
-    let canonical_resource = format!("/{bucket_name}/");
-
-    let request = ObsRequest {
-        method: Method::PUT,
-        url: &url,
-        credentials,
-        body,
-        content_type: Some(ContentType::ApplicationXml),
-        content_md5: "",
-        canonical_resource: &canonical_resource,
-    };
-
-    let response = generate_request(client, request).await?;
-    let status = response.status();
-    let body = response.text().await?;
-
-    log_api_response(status, None::<Vec<String>>, &body).await
-}
+        let mut common_prefixes = xml_to_struct_vec!(
+            CommonPrefix => "CommonPrefixes" in &raw_xml, {
+                Prefix => prefix,
+            }
+        )?;
 
-/// Sends a request to list all OBS buckets.
-pub async fn list_buckets(
-    client: &Client,
-    region: String,
-    credentials: &Credentials,
-) -> Result<()> {
-    let spinner = init_spinner!("Listing all buckets");
-
-    let url = format!("http://obs.{region}.myhuaweicloud.com");
-    let body = Body::Text("".to_string());
-    let canonical_resource = "/";
-
-    let request = ObsRequest {
-        method: Method::GET,
-        url: &url,
-        credentials,
-        body,
-        content_type: None,
-        content_md5: "",
-        canonical_resource,
-    };
-
-    let response = generate_request(client, request).await?;
-
-    let status = response.status(); // extract before consuming
-    let raw_xml = response
-        .text()
-        .await
-        .context("Failed to read response body")?;
-
-    let parsed = xml_to_struct_vec!(
-        BucketList => "Bucket" in &raw_xml, {
-            Name => name,
-            CreationDate => creation_date,
-            Location => location,
-            BucketType => bucket_type
+        let mut next_marker = xml_text(&raw_xml, "NextMarker");
+        if xml_text(&raw_xml, "EncodingType").as_deref() == Some("url") {
+            for object in &mut objects {
+                object.key = endpoint::decode_url(&object.key)?;
+            }
+            for common_prefix in &mut common_prefixes {
+                common_prefix.prefix = endpoint::decode_url(&common_prefix.prefix)?;
+            }
+            next_marker = next_marker
+                .as_deref()
+                .map(endpoint::decode_url)
+                .transpose()?;
         }
-    )
-    // Formatting table output
-    .into_iter()
-    .map(|mut bucket| {
-        bucket.creation_date = make_readable_timestamp(&bucket.creation_date);
-        bucket.location = HUAWEI_CLOUD_REGIONS
-            .iter()
-            .find(|(_, code)| code == &bucket.location)
-            .map(|(name, _)| format!("{} - {}", name, bucket.location))
-            .unwrap_or_else(|| bucket.location.clone());
-        bucket
-    })
-    .collect::<Vec<_>>();
-
-    spinner.finish_with_message("Done");
-    log_api_response(status, Some(parsed), &raw_xml).await
-}
 
-// TODO fix headers to show something useful
-pub async fn list_regions(region_list: &[(&str, &str)]) -> Result<()> {
-    let mut region_table = Table::new(region_list);
+        let is_truncated = xml_text(&raw_xml, "IsTruncated").as_deref() == Some("true");
+        // NextMarker may be omitted, the last key or prefix is the marker of the next page then
+        let next_marker = if is_truncated {
+            next_marker.or_else(|| {
+                let last_key = objects.last().map(|o| o.key.clone());
+                let last_prefix = common_prefixes.last().map(|p| p.prefix.clone());
+                last_key.max(last_prefix)
+            })
+        } else {
+            None
+        };
+
+        Ok(ObjectListPage {
+            objects,
+            common_prefixes,
+            is_truncated,
+            next_marker,
+        })
+    }
 
-    info!(
-        "Available regions:\n{}",
-        region_table.with(Style::rounded())
-    );
-    Ok(())
-}
+    /// Lists objects page by page, following markers until exhaustion or `limit` entries.
+    ///
+    /// Like `max-keys`, the limit counts both objects and common prefixes.
+    ///
+    /// Pages are yielded as they arrive, so callers can show results before the listing ends.
+    pub fn list_objects_pages<'a>(
+        &'a self,
+        bucket_name: &'a str,
+        options: ListObjectsOptions,
+        limit: Option<usize>,
+    ) -> impl Stream<Item = Result<ObjectListPage>> + 'a {
+        stream::try_unfold(
+            (Some(options), limit),
+            move |(options, remaining)| async move {
+                let Some(mut options) = options else {
+                    return Ok(None);
+                };
+                if remaining == Some(0) {
+                    return Ok(None);
+                }
 
-// TODO add object filtering
-/// Sends a request to list all objects in a bucket.
-pub async fn list_objects(
-    client: &Client,
-    bucket_name: &str,
-    prefix: &Option<String>,
-    marker: &Option<String>,
-    region: String,
-    credentials: &Credentials,
-) -> Result<()> {
-    let spinner = init_spinner!(format!("Listing objects in {bucket_name}"));
-
-    let url = format!(
-        "http://{bucket_name}.obs.{region}.myhuaweicloud.com/{}",
-        query_params!(
-            "prefix" => prefix,
-            "marker" => marker,
+                // Don't fetch more keys than needed to reach the limit
+                if let Some(remaining) = remaining {
+                    let page_size = options.max_keys.unwrap_or(MAX_KEYS_PER_PAGE) as usize;
+                    options.max_keys = Some(page_size.min(remaining) as u32);
+                }
+
+                let mut page = self.list_objects_page(bucket_name, &options).await?;
+                let remaining = remaining.map(|r| {
+                    page.objects.truncate(r);
+                    page.common_prefixes.truncate(r - page.objects.len());
+                    r - page.objects.len() - page.common_prefixes.len()
+                });
+
+                let next_options = match &page.next_marker {
+                    Some(marker) if page.is_truncated => {
+                        options.marker = Some(marker.clone());
+                        Some(options)
+                    }
+                    _ => None,
+                };
+
+                Ok(Some((page, (next_options, remaining))))
+            },
         )
-    );
-    let body = Body::Text("".to_string());
-    let canonical_resource = format!("/{bucket_name}/");
-
-    let request = ObsRequest {
-        method: Method::GET,
-        url: &url,
-        credentials,
-        body,
-        content_type: None,
-        content_md5: "",
-        canonical_resource: &canonical_resource,
-    };
-
-    let response = generate_request(client, request).await?;
-
-    let status = response.status(); // extract before consuming
-    let raw_xml = response
-        .text()
-        .await
-        .context("Failed to read response body")?;
-
-    let parsed = xml_to_struct_vec!(
-        ObjectList => "Contents" in &raw_xml, {
-            Key => key,
-            LastModified => last_modified,
-            Size => size,
-            StorageClass => storage_class,
+    }
+
+    /// Lists every object in a bucket (up to `limit`), following pagination markers.
+    ///
+    /// Pages are merged into one, which is truncated only if the limit was reached first.
+    pub async fn list_objects(
+        &self,
+        bucket_name: &str,
+        options: ListObjectsOptions,
+        limit: Option<usize>,
+    ) -> Result<ObjectListPage> {
+        let mut listing = ObjectListPage {
+            objects: Vec::new(),
+            common_prefixes: Vec::new(),
+            is_truncated: false,
+            next_marker: None,
+        };
+
+        let mut pages = std::pin::pin!(self.list_objects_pages(bucket_name, options, limit));
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            listing.objects.extend(page.objects);
+            listing.common_prefixes.extend(page.common_prefixes);
+            listing.is_truncated = page.is_truncated;
+            listing.next_marker = page.next_marker;
         }
-    )
-    // Formatting table output
-    .into_iter()
-    .map(|mut bucket| {
-        bucket.last_modified = make_readable_timestamp(&bucket.last_modified);
-        bucket.size = ByteSize(
-            bucket
-                .size
-                .parse()
-                .expect("Failed to parse file size for {&bucket.key}"),
-        )
-        .to_string();
-        bucket
-    })
-    .collect::<Vec<_>>();
+        Ok(listing)
+    }
 
-    spinner.finish_with_message("Done");
-    log_api_response(status, Some(parsed), &raw_xml).await
-}
+    // TODO QOL Run a "list objects" when the deletion fails
+    /// Deletes a single bucket from OBS
+    pub async fn delete_bucket(&self, bucket_name: &str) -> Result<()> {
+        let url = self.endpoint.url(Some(bucket_name), "", "");
+
+        let body = Body::Text("".to_string());
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), "", "");
+
+        let request = ObsRequest {
+            method: Method::DELETE,
+            url: &url,
+            body,
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        ensure_success(response, "Delete bucket").await?;
+        Ok(())
+    }
 
-fn make_readable_timestamp(timestamp: &str) -> String {
-    chrono::DateTime::parse_from_rfc3339(timestamp)
-        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
-        .unwrap_or_else(|_| timestamp.to_string())
-}
+    /// Deletes multiple buckets asynchronously from OBS
+    ///
+    /// Returns the outcome of every deletion, in the same order as `buckets`.
+    pub async fn delete_buckets(&self, buckets: Vec<String>) -> Vec<(String, Result<()>)> {
+        let delete_futures = buckets
+            .iter()
+            .cloned()
+            .map(|bucket_name| {
+                let obs = self.clone(); // reqwest's Client is an Arc internally which facilitates cloning
+                // Errors shouldn't stop other concurrent deletion tasks
+                tokio::spawn(async move { obs.delete_bucket(&bucket_name).await })
+            })
+            .collect::<Vec<_>>();
+
+        // Wait until all API calls are made
+        let results = join_all(delete_futures).await;
+        buckets
+            .into_iter()
+            .zip(results)
+            .map(|(bucket_name, res)| (bucket_name, res.map_err(Into::into).and_then(|r| r)))
+            .collect()
+    }
 
-// TODO QOL Run a "list objects" when the deletion fails
-/// Deletes a single bucket from OBS
-pub async fn delete_bucket(
-    client: &Client,
-    bucket_name: &str,
-    region: String,
-    credentials: &Credentials,
-) -> Result<()> {
-    let spinner = init_spinner!(format!("Deleting {bucket_name}"));
-    let url = format!("http://{bucket_name}.obs.{region}.myhuaweicloud.com/");
-
-    let body = Body::Text("".to_string());
-    let canonical_resource = format!("/{bucket_name}/");
-
-    let request = ObsRequest {
-        method: Method::DELETE,
-        url: &url,
-        credentials,
-        body,
-        content_type: None,
-        content_md5: "",
-        canonical_resource: &canonical_resource,
-    };
-
-    let response = generate_request(client, request).await?;
-    let status = response.status();
-    let body = response.text().await?;
-
-    spinner.finish_with_message("Done");
-    log_api_response(status, None::<Vec<String>>, &body).await
-}
+    /// Upload an object to a bucket
+    ///
+    /// Returns the key of the uploaded object.
+    pub async fn upload_object(
+        &self,
+        bucket_name: &str,
+        file_path: &str,
+        object_path: Option<&str>,
+        options: &UploadOptions,
+    ) -> Result<String> {
+        let object_name = match object_path {
+            Some(custom_path) => custom_path.to_string(),
+            None => Path::new(file_path)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .map(String::from)
+                .ok_or_else(|| anyhow!("Invalid or missing filename: {}", file_path.blue()))?,
+        };
+
+        let file_size = tokio::fs::metadata(file_path)
+            .await
+            .context("Failed to read file metadata")?
+            .len();
+
+        // Empty files have no parts, so they always take a single PUT
+        if file_size == 0 || file_size < options.multipart_threshold.min(MAX_PUT_SIZE) {
+            let mut buffer = self.buffers.get(file_size as usize).await;
+            let mut file = tokio::fs::File::open(file_path)
+                .await
+                .with_context(|| format!("Failed to open {file_path}"))?;
+            buffer
+                .fill_from(&mut file)
+                .await
+                .with_context(|| format!("Failed to read {file_path}"))?;
+            if buffer.len() as u64 != file_size {
+                return Err(anyhow!("{file_path} was truncated during the upload"));
+            }
 
-/// Deletes multiple buckets asynchronously from OBS
-pub async fn delete_buckets(
-    client: &Client,
-    buckets: Vec<String>,
-    region: String,
-    credentials: &Credentials,
-) -> Result<()> {
-    let delete_futures = buckets
-        .into_iter()
-        .map(|bucket_name| {
-            let client = client.clone(); // reqwest's Client is an Arc internally which facilitates cloning
-            let credentials = credentials.clone();
-            let region = region.to_string();
-            tokio::spawn(async move {
-                // Errors shouldn't stop other concurrent deletion tasks
-                if let Err(e) = delete_bucket(&client, &bucket_name, region, &credentials).await {
-                    error!(
-                        "{} '{}': {}",
-                        "Failed to delete bucket:".red().bold(),
-                        bucket_name,
-                        e
-                    );
+            let content = buffer.freeze();
+            self.put_object(bucket_name, &object_name, content.clone(), options)
+                .await?;
+            buffer.reclaim(content);
+        } else {
+            self.upload_multipart(bucket_name, &object_name, file_path, file_size, options)
+                .await?;
+        }
+        Ok(object_name)
+    }
+
+    // Uploads a file with a single request, OBS checks it against its Content-MD5
+    pub(crate) async fn put_object(
+        &self,
+        bucket_name: &str,
+        object_name: &str,
+        content: Bytes,
+        options: &UploadOptions,
+    ) -> Result<()> {
+        let (content_type, headers) = options.object_headers(object_name)?;
+        let content_md5 = general_purpose::STANDARD.encode(md5::compute(&content).as_ref());
+
+        let url = self.endpoint.url(Some(bucket_name), object_name, "");
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), object_name, "");
+
+        let request = ObsRequest {
+            method: Method::PUT,
+            url: &url,
+            body: Body::Binary(content),
+            content_type: Some(content_type),
+            content_md5: &content_md5,
+            canonical_resource: &canonical_resource,
+            headers,
+        };
+
+        let response = self.generate_request(request).await?;
+        ensure_success(response, "Upload object").await?;
+        Ok(())
+    }
+
+    /// Upload multiple objects to a bucket
+    ///
+    /// Returns the outcome of every upload, in the same order as `file_paths`.
+    pub async fn upload_objects(
+        &self,
+        bucket_name: &str,
+        file_paths: Vec<String>,
+        options: &UploadOptions,
+    ) -> Vec<(String, Result<String>)> {
+        let uploads = file_paths.into_iter().map(|path| (path, None)).collect();
+        self.upload_many(bucket_name, uploads, options).await
+    }
+
+    /// Upload every file under a directory, keys are their relative paths under `prefix`
+    ///
+    /// Returns the outcome of every upload, in the order the files were found.
+    pub async fn upload_directory(
+        &self,
+        bucket_name: &str,
+        dir: &Path,
+        prefix: &str,
+        walk_options: &WalkOptions,
+        options: &UploadOptions,
+    ) -> Result<Vec<(String, Result<String>)>> {
+        let files = walk_directory(dir, prefix, walk_options)?;
+        info!(
+            "Found {} files to upload in {}",
+            files.len().to_string().cyan(),
+            dir.display()
+        );
+
+        let uploads = files
+            .into_iter()
+            .map(|file| (file.path.display().to_string(), Some(file.key)))
+            .collect();
+        Ok(self.upload_many(bucket_name, uploads, options).await)
+    }
+
+    // Uploads (file path, optional key) pairs a few files at a time
+    async fn upload_many(
+        &self,
+        bucket_name: &str,
+        uploads: Vec<(String, Option<String>)>,
+        options: &UploadOptions,
+    ) -> Vec<(String, Result<String>)> {
+        // Follows same logic as other parallel functions
+        stream::iter(uploads)
+            .map(|(file_path, key)| {
+                let obs = self.clone();
+                let bucket_name = bucket_name.to_string();
+                let options = options.clone();
+
+                async move {
+                    let task_path = file_path.clone();
+                    let result = tokio::spawn(async move {
+                        obs.upload_object(&bucket_name, &task_path, key.as_deref(), &options)
+                            .await
+                    })
+                    .await;
+                    (file_path, result.map_err(Into::into).and_then(|r| r))
                 }
             })
-        })
-        .collect::<Vec<_>>();
+            // Keeps the results in order while bounding the files in flight
+            .buffered(FILE_CONCURRENCY)
+            .collect()
+            .await
+    }
 
-    // Wait until all API calls are made
-    join_all(delete_futures).await;
+    /// Download an object from a bucket
+    ///
+    /// Returns the local path the object was written to.
+    pub async fn download_object(
+        &self,
+        bucket_name: &str,
+        object_path: &str,
+        output_dir: Option<&str>,
+        options: &DownloadOptions,
+    ) -> Result<PathBuf> {
+        // Remove first '/' if present
+        let object_path = if let Some(stripped_path) = object_path.strip_prefix('/') {
+            stripped_path
+        } else {
+            object_path
+        };
+
+        // Extracts object file name
+        let filename = Path::new(object_path).file_name().ok_or_else(|| {
+            anyhow!(
+                "Could not determine filename from object path: {}",
+                object_path.yellow()
+            )
+        })?;
+
+        let output_directory = output_dir.unwrap_or(".");
+        let mut local_path = PathBuf::from(output_directory);
+        local_path.push(filename);
+
+        let bar = transfer_bar(0, "");
+        self.download_to(bucket_name, object_path, &local_path, options, &bar, None)
+            .await?;
+        bar.finish_with_message("Done");
+
+        Ok(local_path)
+    }
 
-    Ok(())
-}
+    /// Download every object under a prefix, mirroring keys as a local directory tree
+    ///
+    /// Paths are relative to the prefix's last `/`, so `logs/2026/` puts `logs/2026/a/b.txt`
+    /// into `{output_dir}/a/b.txt`. Returns the outcome of every download, in key order.
+    pub async fn download_prefix(
+        &self,
+        bucket_name: &str,
+        prefix: &str,
+        output_dir: Option<&str>,
+        concurrency: usize,
+        options: &DownloadOptions,
+    ) -> Result<Vec<(String, Result<PathBuf>)>> {
+        let list_options = ListObjectsOptions {
+            prefix: Some(prefix.to_string()),
+            ..ListObjectsOptions::default()
+        };
+        let listing = self.list_objects(bucket_name, list_options, None).await?;
+
+        let base = &prefix[..prefix.rfind('/').map_or(0, |i| i + 1)];
+        let output_dir = PathBuf::from(output_dir.unwrap_or("."));
+
+        // Keys ending in '/' are folder placeholders, not files
+        let objects = listing
+            .objects
+            .into_iter()
+            .filter(|object| !object.key.ends_with('/'))
+            .collect::<Vec<_>>();
+        let total_size = objects
+            .iter()
+            .filter_map(|object| object.size.parse::<u64>().ok())
+            .sum();
+        info!(
+            "Found {} objects to download under {}",
+            objects.len().to_string().cyan(),
+            prefix.cyan()
+        );
 
-// FIXME Unicode filename support (percent encoding)
-/// Upload an object to a bucket
-pub async fn upload_object(
-    client: &Client,
-    bucket_name: &str,
-    region: String,
-    file_path: &str,
-    object_path: &Option<String>,
-    credentials: &Credentials,
-) -> Result<()> {
-    let object_name = match object_path {
-        Some(custom_path) => custom_path.clone(),
-        None => Path::new(file_path)
-            .file_name()
-            .and_then(|s| s.to_str())
-            .map(String::from)
-            .ok_or_else(|| anyhow!("Invalid or missing filename: {}", file_path.blue()))?,
-    };
-
-    const PART_SIZE: u64 = 50 * 1024 * 1024;
-    const MAX_PARTS: u32 = 10_000;
-    const SEMAPHORE_SIZE: usize = 32;
-
-    let metadata = tokio::fs::metadata(file_path)
-        .await
-        .context("Failed to read file metadata")?;
-    let file_size = metadata.len();
-
-    let init_url =
-        format!("http://{bucket_name}.obs.{region}.myhuaweicloud.com/{object_name}?uploads");
-
-    let canonical_resource = format!("/{bucket_name}/{object_name}?uploads");
-
-    let init_request = ObsRequest {
-        method: Method::POST,
-        url: &init_url,
-        credentials,
-        body: Body::Text("".to_string()),
-        content_type: None,
-        content_md5: "",
-        canonical_resource: &canonical_resource,
-    };
-
-    let init_response = generate_request(client, init_request).await?;
-    if !init_response.status().is_success() {
-        let status = init_response.status();
-        let body = init_response.text().await?;
-        return Err(anyhow!("Initiate failed: {status} - {body}",));
-    }
-    let init_body = init_response.text().await?;
-
-    let upload_id = init_body
-        .split("<UploadId>")
-        .nth(1)
-        .and_then(|s| s.split("</UploadId>").next())
-        .ok_or_else(|| anyhow!("Failed to parse UploadId"))?
-        .to_string();
-
-    info!("Starting upload");
-
-    let semaphore = Arc::new(Semaphore::new(SEMAPHORE_SIZE));
-    let bar = Arc::new(ProgressBar::new(file_size));
-    bar.set_style(
-        ProgressStyle::default_bar()
-            .template("[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .expect("Failed to create progress bar template")
-            .progress_chars("##-"),
-    );
+        let multi = MultiProgress::new();
+        let total = multi.add(transfer_bar(total_size, "Total "));
+
+        let results = stream::iter(objects)
+            .map(|object| {
+                let multi = &multi;
+                let total = &total;
+                let output_dir = &output_dir;
+                async move {
+                    let result = async {
+                        let local_path = local_path_for_key(output_dir, &object.key[base.len()..])?;
+                        let size = object.size.parse().unwrap_or(0);
+                        let bar = multi.insert_before(total, transfer_bar(size, &object.key));
+                        bar.set_message(format!("{} ", object.key));
+
+                        let result = self
+                            .download_to(
+                                bucket_name,
+                                &object.key,
+                                &local_path,
+                                options,
+                                &bar,
+                                Some(total),
+                            )
+                            .await;
+                        bar.finish_and_clear();
+                        result.map(|_| local_path)
+                    }
+                    .await;
+                    (object.key, result)
+                }
+            })
+            .buffered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
 
-    let mut part_futures = FuturesUnordered::new();
-
-    let mut offsets = vec![];
-    let mut current_offset = 0;
-    let mut part_number = 1;
-    while current_offset < file_size {
-        let size = std::cmp::min(PART_SIZE, file_size - current_offset);
-        offsets.push((part_number, current_offset, size));
-        current_offset += size;
-        part_number += 1;
-        if part_number > MAX_PARTS {
-            return Err(anyhow!("Too many parts, exceeded {}", MAX_PARTS));
-        }
+        total.finish_with_message("Done ");
+        Ok(results)
     }
 
-    let shared_credentials = Arc::new(credentials.clone());
-    let shared_client = client.clone();
-
-    for (part_number, offset, size) in offsets {
-        let permit = semaphore.clone().acquire_owned().await?;
-        let client = shared_client.clone();
-        let credentials = shared_credentials.clone();
-        let object_name = object_name.clone();
-        let upload_id = upload_id.clone();
-        let region = region.clone();
-        let bucket_name = bucket_name.to_string();
-        let file_path = file_path.to_string();
+    /// Delete an object from a bucket
+    pub async fn delete_object(&self, bucket_name: &str, object_path: &str) -> Result<()> {
+        let url = self.endpoint.url(Some(bucket_name), object_path, "");
+        let body = Body::Text("".to_string());
+        let canonical_resource = endpoint::canonical_resource(Some(bucket_name), object_path, "");
+
+        let request = ObsRequest {
+            method: Method::DELETE,
+            url: &url,
+            body,
+            content_type: None,
+            content_md5: "",
+            canonical_resource: &canonical_resource,
+            headers: HeaderMap::new(),
+        };
+
+        let response = self.generate_request(request).await?;
+        ensure_success(response, "Delete object").await?;
+        Ok(())
+    }
 
-        part_futures.push(tokio::spawn(async move {
-            let _permit = permit;
+    /// Sends a request to OBS, retrying idempotent ones on network errors and throttling.
+    pub(crate) async fn generate_request(&self, req: ObsRequest<'_>) -> Result<Response> {
+        let retryable = req.method.is_idempotent();
+        let max_attempts = self.retry_policy.max_attempts.max(1);
+        let mut attempt = 1;
 
-            let mut file = std::fs::File::open(&file_path)?;
-            file.seek(std::io::SeekFrom::Start(offset))?;
-            let mut buffer = vec![0u8; size as usize];
-            file.read_exact(&mut buffer)?;
+        loop {
+            let result = self.send_signed(&req).await;
+            if !retryable || attempt >= max_attempts {
+                return result;
+            }
 
-            let digest = md5::compute(&buffer);
-            let content_md5 = general_purpose::STANDARD.encode(digest.as_ref());
+            let (delay, reason) = match &result {
+                Ok(response) if is_retryable_status(response.status()) => (
+                    self.retry_policy
+                        .retry_after(response.headers())
+                        .unwrap_or_else(|| self.retry_policy.backoff(attempt)),
+                    response.status().to_string(),
+                ),
+                Ok(_) => return result,
+                Err(e) => (self.retry_policy.backoff(attempt), format!("{e:#}")),
+            };
 
-            let part_url = format!(
-                "http://{bucket_name}.obs.{region}.myhuaweicloud.com/{object_name}?partNumber={part_number}&uploadId={upload_id}",
+            debug!(
+                "Retrying {} {} in {:?} (attempt {}/{}): {}",
+                req.method,
+                req.url,
+                delay,
+                attempt + 1,
+                max_attempts,
+                reason
             );
-            let canonical_resource =
-                format!("/{bucket_name}/{object_name}?partNumber={part_number}&uploadId={upload_id}");
-
-            let part_request = ObsRequest {
-                method: Method::PUT,
-                url: &part_url,
-                credentials: &credentials,
-                body: Body::Binary(buffer),
-                content_type: Some(ContentType::ApplicationOctetStream),
-                content_md5: &content_md5,
-                canonical_resource: &canonical_resource,
-            };
+            drop(result);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
 
-            let response = generate_request(&client, part_request).await?;
-            if !response.status().is_success() {
-                let status = response.status();
-                let body = response.text().await?;
-                return Err(anyhow!(
-                    "Part {part_number} upload failed: {status} - {body}"
-                ));
-            }
+    /// Constructs, signs and sends a single HTTP request to OBS.
+    async fn send_signed(&self, req: &ObsRequest<'_>) -> Result<Response> {
+        // Required date format for OBS
+        let date_str = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let content_type_canonical = req.content_type.as_ref().map_or("", |ct| ct.as_str());
+
+        // Canonical string is used to generate the signature
+        let canonical_string = format!(
+            "{}\n{}\n{}\n{}\n{}{}",              // Newlines are necessary
+            req.method.as_str(),                 // HTTP method
+            req.content_md5,                     // Base64 MD5 hash of body
+            content_type_canonical,              // Optional content type
+            date_str,                            // Timestamp
+            canonical_obs_headers(&req.headers), // x-obs-* headers, each ending in a newline
+            req.canonical_resource,              // Resource path
+        );
 
-            let etag = response
-                .headers()
-                .get("Etag")
-                .ok_or_else(|| anyhow!("Missing ETag for part {}", part_number))?
-                .to_str()?
-                .to_string();
+        debug!("Canonical String for signing:\n{canonical_string}");
 
-            Ok((Part {
-                part_number,
-                etag,
-            }, size as u64))
-        }));
+        // Generate HMAC-SHA1 signature using the canonical string
+        let signature = generate_signature(&self.credentials, &canonical_string)
+            .context("Failed to generate request signature")?;
+
+        // Build OBS-compatible headers
+        let mut headers = req.headers.clone();
+
+        headers.insert("Date", HeaderValue::from_str(&date_str)?);
+        if let Some(ct) = &req.content_type {
+            headers.insert(
+                "Content-Type",
+                HeaderValue::from_str(ct.as_str()).context("Invalid content type")?,
+            );
+        }
+        if !req.content_md5.is_empty() {
+            headers.insert(
+                "Content-MD5",
+                HeaderValue::from_str(req.content_md5)
+                    .context("Couldn't convert content-md5 into string")?,
+            );
+        }
+        // Authorization: OBS <AK>:<Signature>
+        headers.insert(
+            "Authorization",
+            HeaderValue::from_str(&format!("OBS {}:{}", self.credentials.ak, signature))?,
+        );
+
+        // Build request with body
+        let mut req_builder = self
+            .client
+            .request(req.method.clone(), req.url)
+            .headers(headers);
+        // Bodies are cloned since a retried request is sent again
+        req_builder = match &req.body {
+            Body::Text(s) => req_builder.body(s.clone()),
+            Body::Binary(b) => req_builder.body(b.clone()),
+        };
+
+        // Execute the request
+        let res = req_builder
+            .send()
+            .await
+            .context("Failed to send request to OBS endpoint")?;
+
+        Ok(res)
     }
+}
 
-    let mut parts = Vec::new();
-    while let Some(res) = part_futures.next().await {
-        let (part, size) = res??;
-        parts.push(part);
-        bar.inc(size as u64);
+impl ObsClientBuilder {
+    /// Custom endpoint as `host[:port]` or `scheme://host[:port]`, replaces the public one.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
     }
 
-    parts.sort_by_key(|p| p.part_number);
+    pub fn addressing_style(mut self, style: AddressingStyle) -> Self {
+        self.addressing_style = style;
+        self
+    }
 
-    let complete_body = CompleteMultipartUpload { parts };
-    let complete_xml = to_string(&complete_body)?;
-    let complete_url = format!(
-        "http://{bucket_name}.obs.{region}.myhuaweicloud.com/{object_name}?uploadId={upload_id}",
-    );
-    let canonical_resource = format!("/{bucket_name}/{object_name}?uploadId={upload_id}");
-    let complete_request = ObsRequest {
-        method: Method::POST,
-        url: &complete_url,
-        credentials,
-        body: Body::Binary(complete_xml.into_bytes()),
-        content_type: Some(ContentType::ApplicationXml),
-        content_md5: "",
-        canonical_resource: &canonical_resource,
-    };
-
-    let complete_response = generate_request(client, complete_request).await?;
-    let status = complete_response.status();
-    let body = complete_response.text().await?;
-
-    if !status.is_success() {
-        return Err(anyhow!("Complete failed: {} - {}", status, body));
-    }
-
-    log_api_response(status, None::<Vec<String>>, &body).await?;
-    bar.finish_with_message("Done");
-    Ok(())
-}
+    /// Sends requests over plain HTTP, leaving signed requests and data unencrypted.
+    pub fn insecure_http(mut self, insecure_http: bool) -> Self {
+        self.insecure_http = insecure_http;
+        self
+    }
 
-// TODO verify if multithreaded downloads aren't possible
-/// Download an object from a bucket
-pub async fn download_object(
-    client: &Client,
-    bucket_name: &str,
-    region: String,
-    object_path: &str,
-    output_dir: &Option<String>,
-    credentials: &Credentials,
-) -> Result<()> {
-    // Remove first '/' if present
-    let object_path = if let Some(stripped_path) = object_path.strip_prefix('/') {
-        stripped_path
-    } else {
-        object_path
-    };
-
-    let url = format!("http://{bucket_name}.obs.{region}.myhuaweicloud.com/{object_path}");
-    let body = Body::Text("".to_string());
-    let canonical_resource = format!("/{bucket_name}/{object_path}");
-
-    let request = ObsRequest {
-        method: Method::GET,
-        url: &url,
-        credentials,
-        body,
-        content_type: None,
-        content_md5: "",
-        canonical_resource: &canonical_resource,
-    };
-
-    let mut response = generate_request(client, request).await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await?;
-
-        log_api_response(status, None::<Vec<String>>, &body).await?;
-        return Err(anyhow!(
-            "Failed to download object: Server returned non-success status."
-        ));
+    /// PEM bundle of extra root certificates, e.g. for TLS-intercepting proxies.
+    pub fn ca_bundle(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_bundle = Some(path.into());
+        self
     }
 
-    let total_size = response
-        .content_length()
-        .ok_or_else(|| anyhow!("Could not get content length"))?;
-    let bar = ProgressBar::new(total_size);
-    bar.set_style(
-        ProgressStyle::default_bar()
-            .template("[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .expect("Failed to create progress bar template")
-            .progress_chars("##-"),
-    );
+    /// PEM certificate chain and PKCS#8 PEM private key presented to the server.
+    pub fn client_identity(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.client_identity = Some((cert.into(), key.into()));
+        self
+    }
 
-    // Read entire response body into a buffer
-    let mut content = Vec::with_capacity(total_size as usize);
-    while let Some(chunk) = response.chunk().await? {
-        content.extend_from_slice(&chunk);
-        bar.inc(chunk.len() as u64);
+    /// How idempotent requests and multipart parts are retried, see [`RetryPolicy::none`].
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
     }
 
-    // Extracts object file name
-    let filename = Path::new(object_path).file_name().ok_or_else(|| {
-        anyhow!(
-            "Could not determine filename from object path: {}",
-            object_path.yellow()
-        )
-    })?;
+    /// Bytes of file data all uploads may hold in memory at once, see [`DEFAULT_MEMORY_BUDGET`].
+    pub fn memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = bytes;
+        self
+    }
 
-    let output_directory = output_dir.as_deref().unwrap_or(".");
-    let mut local_path = PathBuf::from(output_directory);
+    pub fn build(self) -> Result<ObsClient> {
+        let mut endpoint = match &self.endpoint {
+            Some(endpoint) => Endpoint::parse(endpoint)?,
+            None => Endpoint::for_region(&self.region),
+        }
+        .with_addressing_style(self.addressing_style);
 
-    // Create directories for output path
-    fs::create_dir_all(&local_path)
-        .with_context(|| format!("Failed to create directory for {}", local_path.display()))?;
-    local_path.push(filename);
+        if self.insecure_http {
+            endpoint = endpoint.with_plain_http();
+        }
+        if !endpoint.is_https() {
+            warn!(
+                "Using plain HTTP for {}, requests and object data are not encrypted",
+                endpoint.host().yellow()
+            );
+        }
 
-    // Write object's contents to disk
-    fs::write(&local_path, &content).with_context(|| {
-        format!(
-            "Failed to write downloaded content to {}",
-            local_path.display()
-        )
-    })?;
+        let mut client_builder = Client::builder();
 
-    log::info!(
-        "Successfully downloaded '{}' to '{}'",
-        object_path.cyan(),
-        local_path.display().to_string().green()
-    );
+        if let Some(path) = &self.ca_bundle {
+            let pem = fs::read(path)
+                .with_context(|| format!("Failed to read CA bundle {}", path.display()))?;
+            let certificates = Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid PEM CA bundle {}", path.display()))?;
+            debug!("Loaded {} CA certificates", certificates.len());
+            for certificate in certificates {
+                client_builder = client_builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some((cert_path, key_path)) = &self.client_identity {
+            let cert = fs::read(cert_path).with_context(|| {
+                format!("Failed to read client certificate {}", cert_path.display())
+            })?;
+            let key = fs::read(key_path)
+                .with_context(|| format!("Failed to read client key {}", key_path.display()))?;
+            let identity = Identity::from_pkcs8_pem(&cert, &key)
+                .context("Invalid client certificate or key, expected PEM with a PKCS#8 key")?;
+            client_builder = client_builder.identity(identity);
+        }
+
+        let client = client_builder
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(ObsClient {
+            client,
+            endpoint,
+            region: self.region,
+            credentials: self.credentials,
+            retry_policy: self.retry_policy,
+            buffers: BufferPool::new(self.memory_budget),
+        })
+    }
+}
 
+/// Writes `value` as JSON through a temporary file, so an interruption never leaves half of it.
+pub(crate) fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec(value)?)
+        .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+    fs::rename(&temp_path, path).with_context(|| format!("Failed to write {}", path.display()))?;
     Ok(())
 }
 
-/// Upload multiple objects to a bucket
-pub async fn upload_objects(
-    client: &Client,
-    bucket_name: &str,
-    region: String,
-    file_paths: Vec<String>,
-    credentials: &Credentials,
-) -> Result<()> {
-    // Follows same logic as other parallel functions
-    let upload_futures = file_paths
-        .into_iter()
-        .map(|file_path| {
-            let client = client.clone();
-            let bucket_name = bucket_name.to_string();
-            let region = region.to_string();
-            let credentials = credentials.clone();
-
-            tokio::spawn(async move {
-                if let Err(e) = upload_object(
-                    &client,
-                    &bucket_name,
-                    region,
-                    &file_path,
-                    &None,
-                    &credentials,
-                )
-                .await
-                {
-                    error!("Failed to upload file '{}': {}", file_path.red(), e);
-                } else {
-                    log::info!("Successfully uploaded '{}'", file_path.green());
-                }
-            })
-        })
-        .collect::<Vec<_>>();
+/// Creates a byte progress bar for uploads and downloads, `message` is shown before it.
+pub(crate) fn transfer_bar(len: u64, message: &str) -> ProgressBar {
+    let bar = ProgressBar::new(len);
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{msg}[{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+            .expect("Failed to create progress bar template")
+            .progress_chars("##-"),
+    );
+    bar.set_message(message.to_string());
+    bar
+}
 
-    // Wait until all API calls are made
-    join_all(upload_futures).await;
+/// Returns the response untouched if successful, otherwise its parsed [`ObsError`].
+pub(crate) async fn ensure_success(response: Response, action: &str) -> Result<Response> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
 
-    Ok(())
+    let err = ObsError::from_response(response).await;
+    Err(anyhow::Error::new(err).context(format!("{action} failed")))
 }
 
-/// Delete an object from a bucket
-pub async fn delete_object(
-    client: &Client,
-    bucket_name: &str,
-    region: String,
-    object_path: &str,
-    credentials: &Credentials,
-) -> Result<()> {
-    let spinner = init_spinner!(format!("Deleting object {object_path}"));
-
-    let url = format!("http://{bucket_name}.obs.{region}.myhuaweicloud.com/{object_path}");
-    let body = Body::Text("".to_string());
-    let canonical_resource = format!("/{bucket_name}/{object_path}");
-
-    let request = ObsRequest {
-        method: Method::DELETE,
-        url: &url,
-        credentials,
-        body,
-        content_type: None,
-        content_md5: "",
-        canonical_resource: &canonical_resource,
-    };
-
-    let response = generate_request(client, request).await?;
-    let status = response.status();
-    let body = response.text().await?;
-
-    spinner.finish_with_message("Done");
-
-    log_api_response(status, None::<Vec<String>>, &body).await
+// Signed `x-obs-*` headers, sorted by name with repeated values joined by commas
+fn canonical_obs_headers(headers: &HeaderMap) -> String {
+    let mut names = headers
+        .keys()
+        .map(|name| name.as_str())
+        .filter(|name| name.starts_with("x-obs-"))
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+    names.dedup();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let values = headers
+                .get_all(name)
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).trim().to_string())
+                .collect::<Vec<_>>();
+            format!("{name}:{}\n", values.join(","))
+        })
+        .collect()
 }
 
 /// Computes the HMAC-SHA1 signature for a canonical string.
@@ -706,60 +1093,100 @@ fn generate_signature(credentials: &Credentials, canonical_string: &str) -> Resu
     Ok(general_purpose::STANDARD.encode(mac.finalize().into_bytes()))
 }
 
-/// Constructs and sends a signed HTTP request to OBS.
-async fn generate_request(client: &Client, req: ObsRequest<'_>) -> Result<Response> {
-    // Required date format for OBS
-    let date_str = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
-    let content_type_canonical = req.content_type.as_ref().map_or("", |ct| ct.as_str());
-
-    // Canonical string is used to generate the signature
-    let canonical_string = format!(
-        "{}\n{}\n{}\n{}\n{}",   // Newlines are necessary
-        req.method.as_str(),    // HTTP method
-        req.content_md5,        // Base64 MD5 hash of body
-        content_type_canonical, // Optional content type
-        date_str,               // Timestamp
-        req.canonical_resource, // Resource path
-    );
-
-    debug!("Canonical String for signing:\n{canonical_string}");
-
-    // Generate HMAC-SHA1 signature using the canonical string
-    let signature = generate_signature(req.credentials, &canonical_string)
-        .context("Failed to generate request signature")?;
-
-    // Build OBS-compatible headers
-    let mut headers = HeaderMap::new();
-
-    headers.insert("Date", HeaderValue::from_str(&date_str)?);
-    if let Some(ct) = &req.content_type {
-        headers.insert("Content-Type", HeaderValue::from_static(ct.as_str()));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Answers a single request with `body`, returning the client and the request line received
+    async fn serve_once(body: &'static str) -> (ObsClient, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut chunk = [0; 4096];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                let n = socket.read(&mut chunk).await.unwrap();
+                request.extend_from_slice(&chunk[..n]);
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            let request = String::from_utf8(request).unwrap();
+            request.lines().next().unwrap().to_string()
+        });
+
+        let credentials = Credentials {
+            ak: "ak".to_string(),
+            sk: "sk".to_string(),
+        };
+        let client = ObsClient::builder("la-south-2", credentials)
+            .endpoint(format!("http://{address}"))
+            .addressing_style(AddressingStyle::Path)
+            .build()
+            .unwrap();
+        (client, server)
     }
-    if !req.content_md5.is_empty() {
-        headers.insert(
-            "Content-MD5",
-            HeaderValue::from_str(req.content_md5)
-                .context("Couldn't convert content-md5 into string")?,
-        );
+
+    #[tokio::test]
+    async fn list_objects_page_decodes_url_encoded_listings() {
+        let (client, server) = serve_once(
+            "<ListBucketResult><Name>bkt</Name><Prefix>relat%C3%B3rios%2F</Prefix>\
+             <EncodingType>url</EncodingType><IsTruncated>true</IsTruncated>\
+             <NextMarker>relat%C3%B3rios/c%2B%2B/what%3F%231</NextMarker>\
+             <Contents><Key>relat%C3%B3rios/a%C3%B1o+2025.pdf</Key>\
+             <LastModified>2025-01-01T00:00:00.000Z</LastModified><ETag>\"e\"</ETag>\
+             <Size>3</Size><StorageClass>STANDARD</StorageClass></Contents>\
+             <Contents><Key>relat%C3%B3rios/c%2B%2B/what%3F%231</Key>\
+             <LastModified>2025-01-01T00:00:00.000Z</LastModified><ETag>\"e\"</ETag>\
+             <Size>3</Size><StorageClass>STANDARD</StorageClass></Contents>\
+             <CommonPrefixes><Prefix>relat%C3%B3rios/100%25+done/</Prefix></CommonPrefixes>\
+             </ListBucketResult>",
+        )
+        .await;
+
+        let options = ListObjectsOptions {
+            prefix: Some("relatórios/".to_string()),
+            ..ListObjectsOptions::default()
+        };
+        let page = client.list_objects_page("bkt", &options).await.unwrap();
+        let request_line = server.await.unwrap();
+
+        assert!(request_line.contains("prefix=relat%C3%B3rios%2F"));
+        assert!(request_line.contains("encoding-type=url"));
+        let keys = page
+            .objects
+            .iter()
+            .map(|o| o.key.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["relatórios/año 2025.pdf", "relatórios/c++/what?#1"]);
+        assert_eq!(page.common_prefixes[0].prefix, "relatórios/100% done/");
+        assert_eq!(page.next_marker.as_deref(), Some("relatórios/c++/what?#1"));
     }
-    // Authorization: OBS <AK>:<Signature>
-    headers.insert(
-        "Authorization",
-        HeaderValue::from_str(&format!("OBS {}:{}", req.credentials.ak, signature))?,
-    );
 
-    // Build request with body
-    let mut req_builder = client.request(req.method.clone(), req.url).headers(headers);
-    req_builder = match &req.body {
-        Body::Text(s) => req_builder.body(s.clone()),
-        Body::Binary(b) => req_builder.body(b.clone()),
-    };
+    #[tokio::test]
+    async fn list_objects_page_keeps_plain_listings() {
+        let (client, server) = serve_once(
+            "<ListBucketResult><Name>bkt</Name><IsTruncated>false</IsTruncated>\
+             <Contents><Key>c++/a+b.txt</Key>\
+             <LastModified>2025-01-01T00:00:00.000Z</LastModified><ETag>\"e\"</ETag>\
+             <Size>3</Size><StorageClass>STANDARD</StorageClass></Contents>\
+             </ListBucketResult>",
+        )
+        .await;
 
-    // Execute the request
-    let res = req_builder
-        .send()
-        .await
-        .context("Failed to send request to OBS endpoint")?;
+        let page = client
+            .list_objects_page("bkt", &ListObjectsOptions::default())
+            .await
+            .unwrap();
+        server.await.unwrap();
 
-    Ok(res)
+        assert_eq!(page.objects[0].key, "c++/a+b.txt");
+        assert_eq!(page.next_marker, None);
+    }
 }
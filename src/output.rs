@@ -0,0 +1,386 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use colored::*;
+use log::{error, info};
+use obsctl::region::region_display_name;
+use obsctl::xml::{BucketList, MultipartUploadList, ObjectList, PartList};
+use obsctl::{BucketMetadata, ObjectListPage, ObjectMetadata};
+use serde::Serialize;
+use std::cell::Cell;
+use tabled::{Table, Tabled, settings::style::Style};
+
+/// How results are rendered, tables are logged while the other formats go to stdout.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Ndjson,
+    Csv,
+    Yaml,
+}
+
+/// Outcome of an operation on a single bucket, object or file.
+#[derive(Serialize)]
+pub struct ActionResult {
+    pub action: &'static str,
+    pub target: String,
+    pub success: bool,
+    /// Local path, error message or anything else worth reporting
+    pub detail: Option<String>,
+    #[serde(skip)]
+    message: String,
+}
+
+impl ActionResult {
+    pub fn ok(action: &'static str, target: &str, detail: Option<String>, message: String) -> Self {
+        Self {
+            action,
+            target: target.to_string(),
+            success: true,
+            detail,
+            message,
+        }
+    }
+
+    pub fn failed(action: &'static str, target: &str, err: &anyhow::Error) -> Self {
+        Self {
+            action,
+            target: target.to_string(),
+            success: false,
+            detail: Some(format!("{err:#}")),
+            message: String::new(),
+        }
+    }
+}
+
+/// A named value of a single record, e.g. an object's content type.
+#[derive(Tabled, Serialize)]
+pub struct Field {
+    #[tabled(rename = "Field")]
+    pub name: String,
+    #[tabled(rename = "Value")]
+    pub value: String,
+}
+
+#[derive(Serialize)]
+struct Region<'a> {
+    name: &'a str,
+    project: &'a str,
+}
+
+/// Renders results in the format selected with `--output`.
+pub struct Printer {
+    format: OutputFormat,
+    // CSV headers are only written once, even if rows arrive page by page
+    csv_header_written: Cell<bool>,
+}
+
+impl Printer {
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            csv_header_written: Cell::new(false),
+        }
+    }
+
+    pub fn is_table(&self) -> bool {
+        self.format == OutputFormat::Table
+    }
+
+    /// Printer for results printed as they arrive, JSON becomes NDJSON since separate
+    /// documents wouldn't make a valid JSON value.
+    pub fn streaming(&self) -> Printer {
+        let format = match self.format {
+            OutputFormat::Json => OutputFormat::Ndjson,
+            format => format,
+        };
+        Printer::new(format)
+    }
+
+    /// Prints a list of results, `readable` only formats them for tables.
+    pub fn rows<T: Tabled + Serialize>(
+        &self,
+        rows: Vec<T>,
+        readable: fn(Vec<T>) -> Vec<T>,
+    ) -> Result<()> {
+        match self.format {
+            OutputFormat::Table => log_table(readable(rows)),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+            OutputFormat::Ndjson => {
+                for row in &rows {
+                    println!("{}", serde_json::to_string(row)?);
+                }
+            }
+            OutputFormat::Csv => self.write_csv(&rows)?,
+            OutputFormat::Yaml => print!("---\n{}", serde_yaml::to_string(&rows)?),
+        }
+        Ok(())
+    }
+
+    /// Prints an object listing, folders (common prefixes) included.
+    pub fn listing(&self, page: ObjectListPage) -> Result<()> {
+        match self.format {
+            OutputFormat::Table => {
+                if !page.common_prefixes.is_empty() {
+                    log_table(page.common_prefixes);
+                }
+                log_table(readable_objects(page.objects));
+            }
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&page)?),
+            OutputFormat::Yaml => print!("---\n{}", serde_yaml::to_string(&page)?),
+            OutputFormat::Ndjson => {
+                for prefix in &page.common_prefixes {
+                    println!("{}", serde_json::to_string(prefix)?);
+                }
+                for object in &page.objects {
+                    println!("{}", serde_json::to_string(object)?);
+                }
+            }
+            // Rows must share columns, so folders are rows with only a key
+            OutputFormat::Csv => {
+                let folders = page.common_prefixes.into_iter().map(|p| ObjectList {
+                    key: p.prefix,
+                    last_modified: String::new(),
+                    etag: String::new(),
+                    size: String::new(),
+                    storage_class: String::new(),
+                });
+                self.write_csv(&folders.chain(page.objects).collect::<Vec<_>>())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the outcome of operations, tables log a line per result.
+    pub fn actions(&self, results: Vec<ActionResult>) -> Result<()> {
+        if !self.is_table() {
+            return self.rows_serialized(&results);
+        }
+
+        for result in results {
+            if result.success {
+                log_success(&result.message);
+            } else {
+                error!(
+                    "{} '{}': {}",
+                    format!("Failed to {}:", result.action.replace('-', " "))
+                        .red()
+                        .bold(),
+                    result.target,
+                    result.detail.unwrap_or_default()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints a single record, tables and CSV show `fields` as name/value rows instead.
+    pub fn details<T: Serialize>(&self, record: &T, fields: Vec<Field>) -> Result<()> {
+        match self.format {
+            OutputFormat::Table => log_table(fields),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(record)?),
+            OutputFormat::Ndjson => println!("{}", serde_json::to_string(record)?),
+            OutputFormat::Csv => self.write_csv(&fields)?,
+            OutputFormat::Yaml => print!("---\n{}", serde_yaml::to_string(record)?),
+        }
+        Ok(())
+    }
+
+    pub fn regions(&self, region_list: &[(&str, &str)]) -> Result<()> {
+        if self.is_table() {
+            list_regions(region_list);
+            return Ok(());
+        }
+
+        let regions = region_list
+            .iter()
+            .map(|(name, project)| Region { name, project })
+            .collect::<Vec<_>>();
+        self.rows_serialized(&regions)
+    }
+
+    // Same as `rows` for types that are never shown as tables
+    fn rows_serialized<T: Serialize>(&self, rows: &[T]) -> Result<()> {
+        match self.format {
+            OutputFormat::Table => unreachable!("callers print tables themselves"),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(rows)?),
+            OutputFormat::Ndjson => {
+                for row in rows {
+                    println!("{}", serde_json::to_string(row)?);
+                }
+            }
+            OutputFormat::Csv => self.write_csv(rows)?,
+            OutputFormat::Yaml => print!("---\n{}", serde_yaml::to_string(rows)?),
+        }
+        Ok(())
+    }
+
+    fn write_csv<T: Serialize>(&self, rows: &[T]) -> Result<()> {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(!self.csv_header_written.get())
+            .from_writer(std::io::stdout().lock());
+        for row in rows {
+            writer.serialize(row)?;
+            self.csv_header_written.set(true);
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Logs the result of an operation that doesn't return any data.
+pub fn log_success(message: &str) {
+    info!("{} {}", "Result:".bright_green().bold(), message);
+}
+
+/// Logs a list of results as a table.
+pub fn log_table<T: Tabled>(rows: Vec<T>) {
+    let display_body = if rows.is_empty() {
+        "No entries in response table".bright_yellow().to_string()
+    } else {
+        let mut table = Table::new(rows);
+        table.with(Style::rounded());
+        format!("{table}")
+    };
+
+    info!("{}\n{}", "Result:".bright_green().bold(), display_body);
+}
+
+// TODO fix headers to show something useful
+pub fn list_regions(region_list: &[(&str, &str)]) {
+    let mut region_table = Table::new(region_list);
+
+    info!(
+        "Available regions:\n{}",
+        region_table.with(Style::rounded())
+    );
+}
+
+/// Formats bucket fields for table output
+pub fn readable_buckets(buckets: Vec<BucketList>) -> Vec<BucketList> {
+    buckets
+        .into_iter()
+        .map(|mut bucket| {
+            bucket.creation_date = make_readable_timestamp(&bucket.creation_date);
+            bucket.location = region_display_name(&bucket.location)
+                .map(|name| format!("{} - {}", name, bucket.location))
+                .unwrap_or_else(|| bucket.location.clone());
+            bucket
+        })
+        .collect()
+}
+
+/// Formats object fields for table output
+pub fn readable_objects(objects: Vec<ObjectList>) -> Vec<ObjectList> {
+    objects
+        .into_iter()
+        .map(|mut object| {
+            object.last_modified = make_readable_timestamp(&object.last_modified);
+            if let Ok(size) = object.size.parse() {
+                object.size = bytesize::ByteSize(size).to_string();
+            }
+            object
+        })
+        .collect()
+}
+
+/// Formats multipart upload fields for table output
+pub fn readable_uploads(uploads: Vec<MultipartUploadList>) -> Vec<MultipartUploadList> {
+    uploads
+        .into_iter()
+        .map(|mut upload| {
+            upload.initiated = make_readable_timestamp(&upload.initiated);
+            upload
+        })
+        .collect()
+}
+
+/// Formats part fields for table output
+pub fn readable_parts(parts: Vec<PartList>) -> Vec<PartList> {
+    parts
+        .into_iter()
+        .map(|mut part| {
+            part.last_modified = make_readable_timestamp(&part.last_modified);
+            if let Ok(size) = part.size.parse() {
+                part.size = bytesize::ByteSize(size).to_string();
+            }
+            part
+        })
+        .collect()
+}
+
+fn make_readable_timestamp(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+pub fn readable_object_metadata(object: &ObjectMetadata) -> Vec<Field> {
+    let mut fields = vec![
+        ("Key", Some(object.key.clone())),
+        (
+            "Size",
+            Some(format!(
+                "{} ({} bytes)",
+                bytesize::ByteSize(object.size),
+                object.size
+            )),
+        ),
+        ("Last Modified", object.last_modified.clone()),
+        ("ETag", object.etag.clone()),
+        ("Content Type", object.content_type.clone()),
+        ("Cache Control", object.cache_control.clone()),
+        ("Content Disposition", object.content_disposition.clone()),
+        ("Content Encoding", object.content_encoding.clone()),
+        ("Expires", object.expires.clone()),
+        ("Storage Class", Some(object.storage_class.clone())),
+        ("Version ID", object.version_id.clone()),
+        ("Encryption", object.encryption.clone()),
+        ("Encryption Key", object.encryption_key_id.clone()),
+        ("Restore", object.restore.clone()),
+        ("Expiration", object.expiration.clone()),
+    ]
+    .into_iter()
+    // Headers OBS didn't send aren't worth a row
+    .filter_map(|(name, value)| {
+        value.map(|value| Field {
+            name: name.to_string(),
+            value,
+        })
+    })
+    .collect::<Vec<_>>();
+
+    fields.extend(object.metadata.iter().map(|(name, value)| Field {
+        name: format!("x-obs-meta-{name}"),
+        value: value.clone(),
+    }));
+    fields
+}
+
+pub fn readable_bucket_metadata(bucket: &BucketMetadata) -> Vec<Field> {
+    let exists = if bucket.exists { "yes" } else { "no" };
+    [
+        ("Bucket Name", Some(bucket.name.clone())),
+        ("Exists", Some(exists.to_string())),
+        (
+            "Region",
+            bucket.location.as_ref().map(|location| {
+                region_display_name(location)
+                    .map(|name| format!("{name} - {location}"))
+                    .unwrap_or_else(|| location.clone())
+            }),
+        ),
+        ("Type", bucket.bucket_type.clone()),
+        ("Storage Class", bucket.storage_class.clone()),
+        ("AZ Redundancy", bucket.az_redundancy.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| {
+        value.map(|value| Field {
+            name: name.to_string(),
+            value,
+        })
+    })
+    .collect()
+}
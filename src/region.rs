@@ -0,0 +1,71 @@
+use anyhow::{Result, anyhow};
+use colored::Colorize;
+use log::{debug, info, warn};
+use strsim::levenshtein;
+
+// Maximum allowed edit distance for fuzzy region name matching
+// 4 should be low enough to be unnoticeable
+const LEVENSHTEIN_THRESHOLD: usize = 4;
+
+// Huawei Cloud main regions and their project names
+pub const HUAWEI_CLOUD_REGIONS: &[(&str, &str)] = &[
+    ("santiago", "la-south-2"),
+    ("johannesburg", "af-south-1"),
+    ("bangkok", "ap-southeast-2"),
+    ("hong-kong", "ap-southeast-1"),
+    ("singapore", "ap-southeast-3"),
+    ("beijing", "cn-north-4"),
+    ("guiyang", "cn-southwest-2"),
+    ("shanghai", "cn-east-3"),
+    ("mexico-city", "la-north-1"),
+    ("sao-paulo", "sa-brazil-1"),
+    ("riyadh", "me-west-1"),
+    ("istanbul", "tr-west-1"),
+];
+
+/// Returns the Huawei Cloud project name matching input exactly or approximately.
+pub fn fuzzy_match_region(input_region: &str) -> Result<String> {
+    debug!("Pattern matching region");
+
+    // Try to find an exact match of the project names
+    if let Some((_, code)) = HUAWEI_CLOUD_REGIONS
+        .iter()
+        .find(|(_, code)| code == &input_region)
+    {
+        let name = code.to_string();
+        info!("Exact matched project name {}", name.cyan());
+        Ok(name)
+    } else {
+        // Attempt fuzzy matching using levenshtein distance
+        match HUAWEI_CLOUD_REGIONS
+            .iter()
+            .map(|(name, code)| (levenshtein(input_region, &name.to_lowercase()), code)) // Calculate distance between input and region names
+            .filter(|(dist, _)| *dist <= LEVENSHTEIN_THRESHOLD)                           // Filter matches within allowed threshold
+            .min_by_key(|(dist, _)| *dist)                                                // Pick the closest match
+            .map(|(_, code)| code.to_string())                                            // Extract the equivalent project name
+        {
+            // Fuzzy match found
+            Some(name) => {
+                warn!(
+                    "Fuzzy matched region {} into project name {}",
+                    input_region.yellow(),
+                    name.cyan()
+                );
+                Ok(name)
+            }
+            // Unsatisfactory matches
+            None => Err(anyhow!(
+                "Region '{}' not found or no close match within threshold",
+                input_region.red()
+            )),
+        }
+    }
+}
+
+/// Returns the human-readable name of a project name, if it's a known region.
+pub fn region_display_name(code: &str) -> Option<&'static str> {
+    HUAWEI_CLOUD_REGIONS
+        .iter()
+        .find(|(_, c)| *c == code)
+        .map(|(name, _)| *name)
+}
@@ -0,0 +1,305 @@
+use crate::download::DownloadOptions;
+use crate::obs::{ListObjectsOptions, ObsClient, UploadOptions, transfer_bar};
+use crate::walk::{WalkOptions, local_path_for_key, walk_directory};
+use crate::xml::ObjectList;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use indicatif::MultiProgress;
+use log::debug;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Scheme of bucket locations in sync arguments, e.g. `obs://bucket/logs/`.
+pub const OBS_URL_SCHEME: &str = "obs://";
+
+/// Splits an `obs://bucket/prefix` location into bucket and prefix.
+pub fn parse_obs_url(location: &str) -> Option<(String, String)> {
+    let rest = location.strip_prefix(OBS_URL_SCHEME)?;
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    (!bucket.is_empty()).then(|| (bucket.to_string(), prefix.to_string()))
+}
+
+/// Which way a sync copies files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncDirection {
+    /// Local directory to bucket prefix
+    Upload,
+    /// Bucket prefix to local directory
+    Download,
+}
+
+/// What a sync does with a single file or object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SyncOp {
+    Upload,
+    Download,
+    /// Delete an object missing from the local directory
+    DeleteObject,
+    /// Delete a local file missing from the bucket
+    DeleteFile,
+}
+
+impl SyncOp {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyncOp::Upload => "upload",
+            SyncOp::Download => "download",
+            SyncOp::DeleteObject => "delete-object",
+            SyncOp::DeleteFile => "delete-file",
+        }
+    }
+}
+
+/// A transfer or deletion planned by [`ObsClient::sync_plan`].
+#[derive(Clone, Debug, Serialize)]
+pub struct SyncItem {
+    pub op: SyncOp,
+    pub key: String,
+    pub local_path: PathBuf,
+    /// Why the item is synced, e.g. "new" or "size changed"
+    pub reason: &'static str,
+}
+
+// A local file and the metadata compared against its object
+struct LocalEntry {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+impl ObsClient {
+    /// Compares a local directory with a bucket prefix and lists what syncing them would change.
+    ///
+    /// Files are compared by size first. If the source is newer than the destination, contents
+    /// are compared too when the ETag is a plain MD5, otherwise the newer file is transferred.
+    /// With `delete`, destination entries missing from the source are deleted.
+    pub async fn sync_plan(
+        &self,
+        bucket_name: &str,
+        prefix: &str,
+        dir: &Path,
+        direction: SyncDirection,
+        delete: bool,
+    ) -> Result<Vec<SyncItem>> {
+        // "logs" means the logs/ folder, not every key starting with "logs"
+        let prefix = if prefix.is_empty() || prefix.ends_with('/') {
+            prefix.to_string()
+        } else {
+            format!("{prefix}/")
+        };
+
+        let local = local_entries(dir, &prefix, direction)?;
+        let options = ListObjectsOptions {
+            prefix: Some(prefix.clone()).filter(|p| !p.is_empty()),
+            ..ListObjectsOptions::default()
+        };
+        let remote = self
+            .list_objects(bucket_name, options, None)
+            .await?
+            .objects
+            .into_iter()
+            .filter(|object| !object.key.ends_with('/'))
+            .map(|object| (object.key.clone(), object))
+            .collect::<BTreeMap<_, _>>();
+        debug!(
+            "Comparing {} local files with {} objects",
+            local.len(),
+            remote.len()
+        );
+
+        let mut items = Vec::new();
+        match direction {
+            SyncDirection::Upload => {
+                for (key, entry) in &local {
+                    let reason = match remote.get(key) {
+                        Some(object) => change_reason(entry, object, direction)?,
+                        None => Some("new"),
+                    };
+                    if let Some(reason) = reason {
+                        items.push(SyncItem {
+                            op: SyncOp::Upload,
+                            key: key.clone(),
+                            local_path: entry.path.clone(),
+                            reason,
+                        });
+                    }
+                }
+                if delete {
+                    items.extend(
+                        remote
+                            .keys()
+                            .filter(|k| !local.contains_key(*k))
+                            .map(|key| SyncItem {
+                                op: SyncOp::DeleteObject,
+                                key: key.clone(),
+                                local_path: PathBuf::new(),
+                                reason: "not in source",
+                            }),
+                    );
+                }
+            }
+            SyncDirection::Download => {
+                for (key, object) in &remote {
+                    let reason = match local.get(key) {
+                        Some(entry) => change_reason(entry, object, direction)?,
+                        None => Some("new"),
+                    };
+                    if let Some(reason) = reason {
+                        items.push(SyncItem {
+                            op: SyncOp::Download,
+                            key: key.clone(),
+                            local_path: local_path_for_key(dir, &key[prefix.len()..])?,
+                            reason,
+                        });
+                    }
+                }
+                if delete {
+                    items.extend(
+                        local
+                            .into_iter()
+                            .filter(|(key, _)| !remote.contains_key(key))
+                            .map(|(key, entry)| SyncItem {
+                                op: SyncOp::DeleteFile,
+                                key,
+                                local_path: entry.path,
+                                reason: "not in source",
+                            }),
+                    );
+                }
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Applies a plan from [`ObsClient::sync_plan`], running up to `concurrency` items at a time.
+    ///
+    /// Files are uploaded with `upload_options` and objects downloaded with `download_options`.
+    /// Returns the outcome of every item, in plan order.
+    pub async fn apply_sync(
+        &self,
+        bucket_name: &str,
+        items: Vec<SyncItem>,
+        concurrency: usize,
+        upload_options: &UploadOptions,
+        download_options: &DownloadOptions,
+    ) -> Vec<(SyncItem, Result<()>)> {
+        let multi = MultiProgress::new();
+
+        stream::iter(items)
+            .map(|item| {
+                let multi = &multi;
+                async move {
+                    let result = match item.op {
+                        SyncOp::Upload => match item.local_path.to_str() {
+                            Some(path) => self
+                                .upload_object(bucket_name, path, Some(&item.key), upload_options)
+                                .await
+                                .map(drop),
+                            None => {
+                                Err(anyhow!("{} is not valid UTF-8", item.local_path.display()))
+                            }
+                        },
+                        SyncOp::Download => {
+                            let bar = multi.add(transfer_bar(0, ""));
+                            bar.set_message(format!("{} ", item.key));
+                            let result = self
+                                .download_to(
+                                    bucket_name,
+                                    &item.key,
+                                    &item.local_path,
+                                    download_options,
+                                    &bar,
+                                    None,
+                                )
+                                .await;
+                            bar.finish_and_clear();
+                            result
+                        }
+                        SyncOp::DeleteObject => self.delete_object(bucket_name, &item.key).await,
+                        SyncOp::DeleteFile => {
+                            fs::remove_file(&item.local_path).with_context(|| {
+                                format!("Failed to delete {}", item.local_path.display())
+                            })
+                        }
+                    };
+                    (item, result)
+                }
+            })
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+}
+
+// Files under `dir` by the key they map to
+//
+// A download may create `dir`, so it's empty if missing. A missing upload source is an error,
+// with --delete it would otherwise empty the prefix.
+fn local_entries(
+    dir: &Path,
+    prefix: &str,
+    direction: SyncDirection,
+) -> Result<BTreeMap<String, LocalEntry>> {
+    if direction == SyncDirection::Download && !dir.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    walk_directory(dir, prefix, &WalkOptions::default())?
+        .into_iter()
+        .map(|file| {
+            let metadata = fs::metadata(&file.path)
+                .with_context(|| format!("Failed to read metadata of {}", file.path.display()))?;
+            let entry = LocalEntry {
+                path: file.path,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            };
+            Ok((file.key, entry))
+        })
+        .collect()
+}
+
+// Why the source side of a file/object pair must be copied over, None if they match
+fn change_reason(
+    local: &LocalEntry,
+    remote: &ObjectList,
+    direction: SyncDirection,
+) -> Result<Option<&'static str>> {
+    if remote.size.parse::<u64>().ok() != Some(local.size) {
+        return Ok(Some("size changed"));
+    }
+
+    // LastModified has at most millisecond precision, compare whole seconds
+    let local_modified = DateTime::<Utc>::from(local.modified).timestamp();
+    let source_is_newer = match DateTime::parse_from_rfc3339(&remote.last_modified) {
+        Ok(remote_modified) => match direction {
+            SyncDirection::Upload => local_modified > remote_modified.timestamp(),
+            SyncDirection::Download => remote_modified.timestamp() > local_modified,
+        },
+        Err(_) => true,
+    };
+    if !source_is_newer {
+        return Ok(None);
+    }
+
+    // Single PUT objects have the MD5 of their content as ETag, multipart ones end in "-N"
+    let etag = remote.etag.trim_matches('"');
+    if etag.len() == 32 && !etag.contains('-') {
+        let mut file = fs::File::open(&local.path)
+            .with_context(|| format!("Failed to open {}", local.path.display()))?;
+        let mut context = md5::Context::new();
+        io::copy(&mut file, &mut context)
+            .with_context(|| format!("Failed to read {}", local.path.display()))?;
+        let digest = format!("{:x}", context.compute());
+        return Ok((digest != etag).then_some("content changed"));
+    }
+
+    Ok(Some("newer"))
+}
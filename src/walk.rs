@@ -0,0 +1,215 @@
+use crate::download::is_partial_download;
+use anyhow::{Context, Result, anyhow, bail};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use log::{debug, warn};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use walkdir::WalkDir;
+
+/// What to do with symbolic links found while walking a directory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Upload the file or directory the link points to
+    #[default]
+    Follow,
+    /// Ignore links entirely
+    Skip,
+}
+
+impl FromStr for SymlinkPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "follow" => Ok(SymlinkPolicy::Follow),
+            "skip" => Ok(SymlinkPolicy::Skip),
+            other => Err(anyhow!(
+                "Unknown symlink policy '{other}', expected 'follow' or 'skip'"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SymlinkPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymlinkPolicy::Follow => write!(f, "follow"),
+            SymlinkPolicy::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+/// Filters for walking a local directory tree.
+#[derive(Clone, Debug, Default)]
+pub struct WalkOptions {
+    /// Only files whose relative path matches one of these globs, all files if empty
+    pub include: Vec<String>,
+    /// Files whose relative path matches any of these globs are left out
+    pub exclude: Vec<String>,
+    pub symlinks: SymlinkPolicy,
+}
+
+/// A file found while walking a directory and the object key it maps to.
+#[derive(Clone, Debug)]
+pub struct LocalFile {
+    pub path: PathBuf,
+    /// Path relative to the walked directory, always with `/` separators
+    pub relative_path: String,
+    pub key: String,
+}
+
+/// Lists every file under `dir`, mapping relative paths to keys under `prefix`.
+pub fn walk_directory(dir: &Path, prefix: &str, options: &WalkOptions) -> Result<Vec<LocalFile>> {
+    if !dir.is_dir() {
+        return Err(anyhow!("{} is not a directory", dir.display()));
+    }
+
+    let include = build_globset(&options.include)?;
+    let exclude = build_globset(&options.exclude)?;
+
+    let mut files = Vec::new();
+    let walker = WalkDir::new(dir)
+        .follow_links(options.symlinks == SymlinkPolicy::Follow)
+        .sort_by_file_name();
+
+    for entry in walker {
+        let entry = entry.with_context(|| format!("Failed to walk {}", dir.display()))?;
+
+        if entry.path_is_symlink() && options.symlinks == SymlinkPolicy::Skip {
+            debug!("Skipping symlink {}", entry.path().display());
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        if entry.file_name().to_str().is_some_and(is_partial_download) {
+            debug!("Skipping partial download {}", entry.path().display());
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(dir)?;
+        let Some(relative_path) = relative_key(relative) else {
+            warn!("Skipping non UTF-8 path {}", entry.path().display());
+            continue;
+        };
+
+        if include
+            .as_ref()
+            .is_some_and(|set| !set.is_match(&relative_path))
+            || exclude
+                .as_ref()
+                .is_some_and(|set| set.is_match(&relative_path))
+        {
+            debug!("Filtered out {relative_path}");
+            continue;
+        }
+
+        files.push(LocalFile {
+            path: entry.path().to_path_buf(),
+            key: join_key(prefix, &relative_path),
+            relative_path,
+        });
+    }
+
+    Ok(files)
+}
+
+/// Joins a key prefix and a relative path, adding a `/` between them if missing.
+pub fn join_key(prefix: &str, relative_path: &str) -> String {
+    if prefix.is_empty() || prefix.ends_with('/') {
+        format!("{prefix}{relative_path}")
+    } else {
+        format!("{prefix}/{relative_path}")
+    }
+}
+
+/// Maps a key (relative to a prefix) to a path under `dir`, refusing keys that would escape it.
+pub fn local_path_for_key(dir: &Path, relative_key: &str) -> Result<PathBuf> {
+    let mut path = dir.to_path_buf();
+    let mut has_file_name = false;
+
+    for part in relative_key.split('/').filter(|part| !part.is_empty()) {
+        if part == "." || part == ".." || part.contains(std::path::MAIN_SEPARATOR) {
+            bail!(
+                "Refusing to write key '{relative_key}' outside of {}",
+                dir.display()
+            );
+        }
+        path.push(part);
+        has_file_name = true;
+    }
+
+    if !has_file_name {
+        bail!("Key '{relative_key}' doesn't map to a file name");
+    }
+    Ok(path)
+}
+
+// Keys always use '/', whatever the platform separator is
+fn relative_key(relative: &Path) -> Option<String> {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()
+        .map(|parts| parts.join("/"))
+}
+
+/// Builds a set matching whole '/'-separated paths, `*` stays within a folder and `**` crosses them.
+/// Returns None if there are no patterns.
+pub(crate) fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob '{pattern}'"))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_path_for_key_keeps_nested_keys_under_dir() {
+        let dir = Path::new("out");
+        assert_eq!(
+            local_path_for_key(dir, "logs/2025/app.log").unwrap(),
+            dir.join("logs").join("2025").join("app.log")
+        );
+        // Empty parts from doubled or leading slashes are dropped
+        assert_eq!(
+            local_path_for_key(dir, "/logs//app.log").unwrap(),
+            dir.join("logs").join("app.log")
+        );
+    }
+
+    #[test]
+    fn local_path_for_key_refuses_traversal() {
+        let dir = Path::new("out");
+        for key in [
+            "..",
+            "../secret",
+            "logs/../../secret",
+            "logs/./app.log",
+            "a/..",
+        ] {
+            assert!(local_path_for_key(dir, key).is_err(), "{key}");
+        }
+    }
+
+    #[test]
+    fn local_path_for_key_needs_a_file_name() {
+        let dir = Path::new("out");
+        for key in ["", "/", "//"] {
+            assert!(local_path_for_key(dir, key).is_err(), "{key}");
+        }
+    }
+}
@@ -1,11 +1,9 @@
-// FIXME failed calls spit different xml structures
-
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 // Creates a struct with the repeated fields in the XML response
 macro_rules! xml_table {
     ($struct_name:ident { $($renamed_field:expr => $table_field:ident : $t:ty),* $(,)? }) => {
-        #[derive(tabled::Tabled)]
+        #[derive(tabled::Tabled, Serialize)]
         pub struct $struct_name {
             $( // Repeats this for every field
                 #[tabled(rename = $renamed_field)]
@@ -14,40 +12,54 @@ macro_rules! xml_table {
         }
     };
 }
-/// Parses XML into a vector of structs.
+/// Parses XML into a vector of structs, failing only if the XML itself is invalid.
+///
+/// Error responses are handled before this, see [`crate::error::ObsError`].
 #[macro_export]
 macro_rules! xml_to_struct_vec {
     (
         $table_type:ident => $repeated_field:literal in $xml:expr, { $($xml_tag:ident => $field:ident),* $(,)? }
     ) => {{
-        // Unless the API somehow returns invalid XML, this can't break
-        let doc = roxmltree::Document::parse($xml).unwrap();
-
-        // Iterate over elements matching the repeated field name
-        doc.descendants()
-            .filter(|n| n.has_tag_name($repeated_field))
-            .map(|node| {
-                // Extract the text of each child element matching the given tag name
-                $(
-                    let $field = node
-                        .descendants()
-                        .find(|n| n.has_tag_name(stringify!($xml_tag)))
-                        .and_then(|n| n.text())
-                        .unwrap_or("")
-                        .to_string();
-                )*
-                // Construct an instance of the struct using the extracted fields
-                $table_type {
+        // The API can return invalid XML (e.g. a proxy error page), report it instead of panicking
+        match roxmltree::Document::parse($xml) {
+            Ok(doc) => Ok(doc
+                // Iterate over elements matching the repeated field name
+                .descendants()
+                .filter(|n| n.has_tag_name($repeated_field))
+                .map(|node| {
+                    // Extract the text of each child element matching the given tag name
                     $(
-                        $field: $field,
+                        let $field = node
+                            .descendants()
+                            .find(|n| n.has_tag_name(stringify!($xml_tag)))
+                            .and_then(|n| n.text())
+                            .unwrap_or("")
+                            .to_string();
                     )*
-                }
-            })
-            // Collect all constructed structs into a vector
-            .collect::<Vec<$table_type>>()
+                    // Construct an instance of the struct using the extracted fields
+                    $table_type {
+                        $(
+                            $field: $field,
+                        )*
+                    }
+                })
+                // Collect all constructed structs into a vector
+                .collect::<Vec<$table_type>>()),
+            Err(e) => Err(anyhow::anyhow!("Invalid XML in OBS response: {e}")),
+        }
     }};
 }
 
+/// Returns the text of a top-level element of the response, e.g. `IsTruncated`.
+pub fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let doc = roxmltree::Document::parse(xml).ok()?;
+    doc.root_element()
+        .children()
+        .find(|n| n.has_tag_name(tag))
+        .and_then(|n| n.text())
+        .map(String::from)
+}
+
 // REVIEW these are only useful for requests that return XML content
 
 xml_table! {
@@ -63,15 +75,64 @@ xml_table! {
     ObjectList {
         "Key (Object Path)" => key: String,
         "Last Modified" => last_modified: String,
-        // Didn't bother to add Etag but it can be easily added here
+        "ETag" => etag: String,
         "Size" => size: String,
         // REVIEW Owner tag contains a nested id
         "Storage Class" => storage_class: String,
     }
 }
 
+xml_table! {
+    CommonPrefix {
+        "Prefix (Folder)" => prefix: String,
+    }
+}
+
+xml_table! {
+    DeleteError {
+        "Key (Object Path)" => key: String,
+        "Code" => code: String,
+        "Message" => message: String,
+    }
+}
+
+// Body of a Delete Multiple Objects request
+#[derive(Serialize)]
+#[serde(rename = "Delete")]
+pub struct DeleteObjects {
+    // Only failures are listed in the response
+    #[serde(rename = "Quiet")]
+    pub quiet: bool,
+    #[serde(rename = "Object")]
+    pub objects: Vec<ObjectToDelete>,
+}
+
+#[derive(Serialize)]
+pub struct ObjectToDelete {
+    #[serde(rename = "Key")]
+    pub key: String,
+}
+
 // Multipart uploading
 
+xml_table! {
+    MultipartUploadList {
+        "Key (Object Path)" => key: String,
+        "Upload ID" => upload_id: String,
+        "Initiated" => initiated: String,
+        "Storage Class" => storage_class: String,
+    }
+}
+
+xml_table! {
+    PartList {
+        "Part Number" => part_number: String,
+        "Last Modified" => last_modified: String,
+        "ETag" => etag: String,
+        "Size" => size: String,
+    }
+}
+
 // The entire multipart upload is composed of parts
 #[derive(Serialize)]
 pub struct CompleteMultipartUpload {
@@ -80,7 +141,7 @@ pub struct CompleteMultipartUpload {
 }
 
 // Part of the whole upload
-#[derive(Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Part {
     #[serde(rename = "PartNumber")]
     pub part_number: u32,